@@ -0,0 +1,36 @@
+//! Aliases module holds the aliases defined with `alias`, expanded by the lexer
+
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref ALIASES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+}
+
+/// Whether the name can be given to an alias
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|ch| ch.is_whitespace() || "/$`='\"\\;&|<>()".contains(ch))
+}
+
+pub fn get(name: &str) -> Option<String> {
+    ALIASES.lock().unwrap().get(name).cloned()
+}
+
+pub fn set(name: &str, value: &str) {
+    ALIASES.lock().unwrap().insert(name.to_string(), value.to_string());
+}
+
+/// Removes the alias, returning whether there was one
+pub fn unset(name: &str) -> bool {
+    ALIASES.lock().unwrap().remove(name).is_some()
+}
+
+pub fn clear() {
+    ALIASES.lock().unwrap().clear();
+}
+
+/// Every alias, sorted by name
+pub fn all() -> Vec<(String, String)> {
+    ALIASES.lock().unwrap().iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+}
@@ -0,0 +1,123 @@
+//! Arith module evaluates the integer expressions assigned to `declare -i` variables
+
+use crate::variables;
+use std::iter::Peekable;
+use std::str::Chars;
+
+struct Evaluator<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Evaluator<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|ch| ch.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn next_if(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    // <expr> ::= <term> { ('+' | '-') <term> }
+    fn expr(&mut self) -> Result<i64, String> {
+        let mut value = self.term()?;
+        loop {
+            if self.next_if('+') {
+                value = value.wrapping_add(self.term()?);
+            } else if self.next_if('-') {
+                value = value.wrapping_sub(self.term()?);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // <term> ::= <factor> { ('*' | '/' | '%') <factor> }
+    fn term(&mut self) -> Result<i64, String> {
+        let mut value = self.factor()?;
+        loop {
+            if self.next_if('*') {
+                value = value.wrapping_mul(self.factor()?);
+            } else if self.next_if('/') {
+                let divisor = self.factor()?;
+                value = value.checked_div(divisor).ok_or("division by 0")?;
+            } else if self.next_if('%') {
+                let divisor = self.factor()?;
+                value = value.checked_rem(divisor).ok_or("division by 0")?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    // <factor> ::= ('-' | '+') <factor> | '(' <expr> ')' | <number> | <name>
+    fn factor(&mut self) -> Result<i64, String> {
+        if self.next_if('-') {
+            return Ok(self.factor()?.wrapping_neg());
+        }
+        if self.next_if('+') {
+            return self.factor();
+        }
+        if self.next_if('(') {
+            let value = self.expr()?;
+            return if self.next_if(')') {
+                Ok(value)
+            } else {
+                Err("missing `)'".to_string())
+            };
+        }
+
+        self.skip_whitespace();
+        let mut operand = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !(ch.is_ascii_alphanumeric() || ch == '_') {
+                break;
+            }
+            operand.push(ch);
+            self.chars.next();
+        }
+
+        if operand.is_empty() {
+            Err("operand expected".to_string())
+        } else if operand.starts_with(|ch: char| ch.is_ascii_digit()) {
+            operand
+                .parse()
+                .map_err(|_| format!("{}: value too great for base", operand))
+        } else if variables::is_valid_name(&operand) {
+            match variables::get(&operand) {
+                Some(ref value) if !value.trim().is_empty() => evaluate(value),
+                _ => Ok(0),
+            }
+        } else {
+            Err(format!("{}: invalid operand", operand))
+        }
+    }
+}
+
+pub fn evaluate(expression: &str) -> Result<i64, String> {
+    let mut evaluator = Evaluator {
+        chars: expression.chars().peekable(),
+    };
+    let value = evaluator.expr()?;
+    evaluator.skip_whitespace();
+    match evaluator.chars.next() {
+        None => Ok(value),
+        Some(ch) => Err(format!("{}: syntax error in expression (error token is \"{}\")", expression, ch)),
+    }
+}
+
+#[test]
+fn test_evaluate() {
+    assert_eq!(evaluate("1 + 2 * 3"), Ok(7));
+    assert_eq!(evaluate("(1 + 2) * -3"), Ok(-9));
+    assert_eq!(evaluate("7 / 2 + 7 % 2"), Ok(4));
+    assert!(evaluate("1 / 0").is_err());
+    assert!(evaluate("1 +").is_err());
+}
@@ -0,0 +1,62 @@
+//! `alias` and `unalias` builtins, defining the aliases the lexer expands
+
+use super::declare::parse_options;
+use crate::quote::quote;
+use crate::aliases;
+
+/// `alias [-p] [name[=value] ...]`, defining aliases or printing them
+pub fn alias(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("alias", args, "p") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    if names.is_empty() || options.contains(&('p', true)) {
+        for (name, value) in aliases::all() {
+            println!("alias {}={}", name, quote(&value));
+        }
+    }
+
+    let mut status = 0;
+    for arg in names {
+        match arg.find('=') {
+            Some(n) if aliases::is_valid_name(&arg[..n]) => aliases::set(&arg[..n], &arg[n + 1..]),
+            Some(n) => {
+                eprintln!("mysh: alias: `{}': invalid alias name", &arg[..n]);
+                status = 1;
+            }
+            None => match aliases::get(arg) {
+                Some(value) => println!("alias {}={}", arg, quote(&value)),
+                None => {
+                    eprintln!("mysh: alias: {}: not found", arg);
+                    status = 1;
+                }
+            },
+        }
+    }
+    status
+}
+
+/// `unalias [-a] name [name ...]`
+pub fn unalias(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("unalias", args, "a") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    if options.contains(&('a', true)) {
+        aliases::clear();
+        return 0;
+    }
+    if names.is_empty() {
+        eprintln!("unalias: usage: unalias [-a] name [name ...]");
+        return 2;
+    }
+
+    let mut status = 0;
+    for name in names {
+        if !aliases::unset(name) {
+            eprintln!("mysh: unalias: {}: not found", name);
+            status = 1;
+        }
+    }
+    status
+}
@@ -0,0 +1,166 @@
+//! `cd` and `pwd` builtins, changing the working directory and maintaining `PWD` and `OLDPWD`
+
+use crate::variables;
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+/// Resolves `.` and `..` components textually, the way `cd -L` follows the path typed
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// relative directories not starting with `.` or `..` are searched in CDPATH,
+// returning whether a non-empty CDPATH entry matched
+fn search_cdpath(dir: &str) -> Option<(PathBuf, bool)> {
+    let first = Path::new(dir).components().next();
+    if !matches!(first, Some(Component::Normal(_))) {
+        return None;
+    }
+    let cdpath = variables::get("CDPATH")?;
+    cdpath.split(':').find_map(|entry| {
+        let candidate = Path::new(if entry.is_empty() { "." } else { entry }).join(dir);
+        if candidate.is_dir() {
+            Some((candidate, !entry.is_empty()))
+        } else {
+            None
+        }
+    })
+}
+
+// current directory as the shell tracks it, from `PWD` when that names it
+fn current_dir() -> PathBuf {
+    let physical = env::current_dir().unwrap_or_default();
+    match variables::get("PWD").map(PathBuf::from) {
+        Some(pwd) if pwd.is_absolute() && pwd.canonicalize().ok() == physical.canonicalize().ok() => pwd,
+        _ => physical,
+    }
+}
+
+/// Sets `PWD` at startup, keeping an inherited value only when it names the current directory
+pub fn init_pwd() {
+    variables::set("PWD", &current_dir().to_string_lossy()).ok();
+}
+
+/// `cd [-L|-P] [dir]`
+pub fn cd(args: &[String]) -> i32 {
+    let mut physical = false;
+    let mut args = args;
+    while let Some(arg) = args.first() {
+        match &arg[..] {
+            "-L" => physical = false,
+            "-P" => physical = true,
+            "--" => {
+                args = &args[1..];
+                break;
+            }
+            _ => break,
+        }
+        args = &args[1..];
+    }
+
+    let (dir, mut print) = match args {
+        [] => match variables::get("HOME") {
+            Some(home) => (home, false),
+            None => {
+                eprintln!("mysh: cd: HOME not set");
+                return 1;
+            }
+        },
+        [dir] if dir == "-" => match variables::get("OLDPWD") {
+            Some(oldpwd) => (oldpwd, true),
+            None => {
+                eprintln!("mysh: cd: OLDPWD not set");
+                return 1;
+            }
+        },
+        [dir] => (dir.clone(), false),
+        _ => {
+            eprintln!("mysh: cd: too many arguments");
+            return 1;
+        }
+    };
+
+    let target = match search_cdpath(&dir) {
+        Some((found, from_cdpath)) => {
+            print |= from_cdpath;
+            found
+        }
+        None => PathBuf::from(&dir),
+    };
+    let oldpwd = current_dir();
+    let logical = normalize(&oldpwd.join(&target));
+
+    // a logical path that can't be followed falls back to the physical one
+    let result = if physical {
+        env::set_current_dir(&target)
+    } else {
+        env::set_current_dir(&logical).or_else(|_| {
+            physical = true;
+            env::set_current_dir(&target)
+        })
+    };
+    if let Err(e) = result {
+        eprintln!("mysh: cd: {}: {}", dir, e);
+        return 1;
+    }
+
+    let pwd = if physical {
+        env::current_dir().unwrap_or(logical)
+    } else {
+        logical
+    };
+    let result = variables::set("OLDPWD", &oldpwd.to_string_lossy())
+        .and_then(|()| variables::set("PWD", &pwd.to_string_lossy()));
+    if let Err(e) = result {
+        eprintln!("mysh: cd: {}", e);
+        return 1;
+    }
+    if print {
+        println!("{}", pwd.display());
+    }
+    0
+}
+
+/// `pwd [-L|-P]`
+pub fn pwd(args: &[String]) -> i32 {
+    let physical = match args.last().map(|arg| &arg[..]) {
+        None | Some("-L") => false,
+        Some("-P") => true,
+        Some(arg) => {
+            eprintln!("mysh: pwd: {}: invalid option", arg);
+            return 2;
+        }
+    };
+    let dir = if physical {
+        env::current_dir()
+    } else {
+        Ok(current_dir())
+    };
+    match dir {
+        Ok(dir) => {
+            println!("{}", dir.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("mysh: pwd: {}", e);
+            1
+        }
+    }
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(normalize(Path::new("/usr/lib/../bin/./")), PathBuf::from("/usr/bin"));
+    assert_eq!(normalize(Path::new("/../tmp")), PathBuf::from("/tmp"));
+    assert_eq!(normalize(Path::new("/a/b/../..")), PathBuf::from("/"));
+}
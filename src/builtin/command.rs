@@ -0,0 +1,149 @@
+//! `type`, `command` and `hash` builtins, reporting how names resolve to commands
+
+use super::declare::parse_options;
+use crate::commands::{self, Resolution};
+use crate::quote::quote;
+
+// `ll is aliased to `ls -l'` and the like, as `type` and `command -V` print it
+fn describe(name: &str, resolution: &Resolution) -> String {
+    match resolution {
+        Resolution::Alias(value) => format!("{} is aliased to `{}'", name, value),
+        Resolution::Builtin => format!("{} is a shell builtin", name),
+        Resolution::File(path, true) => format!("{} is hashed ({})", name, path.display()),
+        Resolution::File(path, false) => format!("{} is {}", name, path.display()),
+    }
+}
+
+// the single word `type -t` prints
+fn kind(resolution: &Resolution) -> &'static str {
+    match resolution {
+        Resolution::Alias(_) => "alias",
+        Resolution::Builtin => "builtin",
+        Resolution::File(..) => "file",
+    }
+}
+
+/// `type [-afptP] name [name ...]`, telling how each name would be run.
+/// `-a` shows every way it could be run, `-t` a single word for it, `-p` and `-P`
+/// only the program, `-P` searching `PATH` even when it names an alias or builtin.
+pub fn r#type(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("type", args, "afptP") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let option = |letter| options.contains(&(letter, true));
+    let (all, terse) = (option('a'), option('t'));
+    let (path_only, force_path) = (option('p') || option('P'), option('P'));
+
+    let mut status = 0;
+    for name in names {
+        let resolutions: Vec<_> = if force_path {
+            let paths = commands::resolve_all(name).into_iter();
+            let paths = paths.filter(|resolution| matches!(resolution, Resolution::File(..)));
+            paths.take(if all { usize::MAX } else { 1 }).collect()
+        } else if all {
+            commands::resolve_all(name)
+        } else {
+            commands::resolve(name).into_iter().collect()
+        };
+        if resolutions.is_empty() {
+            if !terse && !path_only {
+                eprintln!("mysh: type: {}: not found", name);
+            }
+            status = 1;
+        }
+
+        for resolution in &resolutions {
+            if terse {
+                println!("{}", kind(resolution));
+            } else if let (true, Resolution::File(path, _)) = (path_only, resolution) {
+                println!("{}", path.display());
+            } else if !path_only {
+                println!("{}", describe(name, resolution));
+            }
+        }
+    }
+    status
+}
+
+/// `command [-vV] name [arg ...]`. Running the name happens before the builtin is
+/// reached, so only `-v`, printing what would run, and `-V`, describing it, are left to it.
+pub fn command(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("command", args, "vV") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let verbose = options.contains(&('V', true));
+    if !verbose && !options.contains(&('v', true)) {
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        match commands::resolve(name) {
+            Some(resolution) if verbose => println!("{}", describe(name, &resolution)),
+            Some(Resolution::Alias(value)) => println!("alias {}={}", name, quote(&value)),
+            Some(Resolution::Builtin) => println!("{}", name),
+            Some(Resolution::File(path, _)) => println!("{}", path.display()),
+            None => {
+                if verbose {
+                    eprintln!("mysh: command: {}: not found", name);
+                }
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// `hash [-r] [-d] [-t] [name ...]`. Without names the remembered programs are
+/// listed with how often they were run, names are looked up and remembered,
+/// `-t` prints where they are, `-d` forgets them and `-r` forgets everything.
+pub fn hash(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("hash", args, "rdt") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let option = |letter| options.contains(&(letter, true));
+    if option('r') {
+        commands::forget_all();
+    }
+
+    if names.is_empty() {
+        if option('r') {
+            return 0;
+        }
+        let hashed = commands::hashed();
+        if hashed.is_empty() {
+            println!("hash: hash table empty");
+            return 0;
+        }
+        println!("hits\tcommand");
+        for (_, path, hits) in hashed {
+            println!("{:4}\t{}", hits, path.display());
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        let found = if option('d') {
+            commands::forget(name)
+        } else if option('t') {
+            let path = commands::hashed_path(name);
+            match &path {
+                Some(path) if names.len() > 1 => println!("{}\t{}", name, path.display()),
+                Some(path) => println!("{}", path.display()),
+                None => (),
+            }
+            path.is_some()
+        } else {
+            commands::hash(name)
+        };
+        if !found {
+            eprintln!("mysh: hash: {}: not found", name);
+            status = 1;
+        }
+    }
+    status
+}
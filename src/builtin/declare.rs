@@ -0,0 +1,337 @@
+//! Builtins managing shell variables: `declare`, `export`, `readonly` and `unset`
+
+use crate::expand;
+use crate::quote::quote;
+use crate::variables::{self, Value, Variable};
+use std::collections::BTreeMap;
+
+/// Attribute changes requested on the command line, `None` leaving them untouched
+#[derive(Default)]
+struct Attributes {
+    exported: Option<bool>,
+    readonly: Option<bool>,
+    integer: Option<bool>,
+    indexed: bool,
+    associative: bool,
+}
+
+impl Attributes {
+    fn matches(&self, var: &Variable) -> bool {
+        self.exported.is_none_or(|x| x == var.exported)
+            && self.readonly.is_none_or(|r| r == var.readonly)
+            && self.integer.is_none_or(|i| i == var.integer)
+            && (!self.indexed || matches!(var.value, Some(Value::Indexed(_))))
+            && (!self.associative || matches!(var.value, Some(Value::Associative(_))))
+    }
+}
+
+/// Options given to a builtin as (letter, enabled) pairs
+pub type Options = Vec<(char, bool)>;
+
+/// Splits leading `-x`/`+x` style options from the operands.
+/// Returns the options as (letter, enabled) pairs.
+pub fn parse_options<'a>(builtin: &str, args: &'a [String], allowed: &str) -> Result<(Options, &'a [String]), i32> {
+    let mut options = vec![];
+    let mut rest = args;
+    while let Some(arg) = rest.first() {
+        if arg == "--" {
+            rest = &rest[1..];
+            break;
+        }
+        let enabled = arg.starts_with('-');
+        if arg.len() < 2 || !(enabled || arg.starts_with('+')) {
+            break;
+        }
+        for letter in arg[1..].chars() {
+            if !allowed.contains(letter) {
+                eprintln!("mysh: {}: {}{}: invalid option", builtin, &arg[..1], letter);
+                return Err(2);
+            }
+            options.push((letter, enabled));
+        }
+        rest = &rest[1..];
+    }
+    Ok((options, rest))
+}
+
+/// Formats a value the way it is written in an assignment, arrays as `([key]=element ...)`
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::Scalar(value) => quote(value),
+        value => {
+            let elements: Vec<_> = value
+                .keys()
+                .iter()
+                .zip(value.elements())
+                .map(|(key, element)| format!("[{}]={}", key, quote(&element)))
+                .collect();
+            format!("({})", elements.join(" "))
+        }
+    }
+}
+
+/// Formats a variable the way `declare -p` prints it
+pub fn declaration(name: &str, var: &Variable) -> String {
+    let mut flags = String::new();
+    match var.value {
+        Some(Value::Indexed(_)) => flags.push('a'),
+        Some(Value::Associative(_)) => flags.push('A'),
+        _ => (),
+    }
+    if var.integer {
+        flags.push('i');
+    }
+    if var.readonly {
+        flags.push('r');
+    }
+    if var.exported {
+        flags.push('x');
+    }
+    if flags.is_empty() {
+        flags.push('-');
+    }
+
+    match &var.value {
+        Some(value) => format!("declare -{} {}={}", flags, name, format_value(value)),
+        None => format!("declare -{} {}", flags, name),
+    }
+}
+
+fn print_declarations(builtin: &str, names: &[String], filter: &Attributes) -> i32 {
+    if names.is_empty() {
+        for (name, var) in variables::all() {
+            if filter.matches(&var) {
+                println!("{}", declaration(&name, &var));
+            }
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for name in names {
+        match variables::lookup(name) {
+            Some(var) => println!("{}", declaration(name, &var)),
+            None => {
+                eprintln!("mysh: {}: {}: not found", builtin, name);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+// converts the variable into the array kind requested by `-a` or `-A`
+fn make_array(builtin: &str, name: &str, associative: bool) -> Result<(), String> {
+    match (variables::lookup(name).and_then(|var| var.value), associative) {
+        (Some(Value::Indexed(_)), true) => {
+            return Err(format!("{}: {}: cannot convert indexed to associative array", builtin, name))
+        }
+        (Some(Value::Associative(_)), false) => {
+            return Err(format!("{}: {}: cannot convert associative to indexed array", builtin, name))
+        }
+        _ => (),
+    }
+
+    variables::set_attributes(name, |var| {
+        var.value = Some(match var.value.take() {
+            Some(Value::Scalar(s)) if associative => Value::Associative(vec![("0".to_string(), s)].into_iter().collect()),
+            Some(Value::Scalar(s)) => Value::Indexed(vec![(0, s)].into_iter().collect()),
+            Some(value) => value,
+            None if associative => Value::Associative(BTreeMap::new()),
+            None => Value::Indexed(BTreeMap::new()),
+        })
+    });
+    Ok(())
+}
+
+fn declare_names(builtin: &str, args: &[String], attributes: &Attributes) -> i32 {
+    let mut status = 0;
+    for arg in args {
+        let (name, value) = match arg.find('=') {
+            Some(n) => (&arg[..n], Some(&arg[n + 1..])),
+            None => (&arg[..], None),
+        };
+
+        if !variables::is_valid_name(name) {
+            eprintln!("mysh: {}: `{}': not a valid identifier", builtin, arg);
+            status = 1;
+            continue;
+        }
+
+        let is_readonly = variables::lookup(name).is_some_and(|var| var.readonly);
+        if is_readonly
+            && (value.is_some() || attributes.integer.is_some() || attributes.readonly == Some(false))
+        {
+            eprintln!("mysh: {}: {}: readonly variable", builtin, name);
+            status = 1;
+            continue;
+        }
+
+        if attributes.indexed || attributes.associative {
+            if let Err(e) = make_array(builtin, name, attributes.associative) {
+                eprintln!("mysh: {}", e);
+                status = 1;
+                continue;
+            }
+        }
+
+        // the integer attribute applies to the value being assigned
+        if let Some(integer) = attributes.integer {
+            variables::set_attributes(name, |var| var.integer = integer);
+        }
+        if let Some(value) = value {
+            // `name=(...)` arguments reach us unexpanded
+            let result = if expand::is_array_literal_assignment(arg) {
+                expand::expand_assignment(arg).and_then(|assignment| variables::assign(&assignment))
+            } else {
+                variables::set(name, value)
+            };
+            if let Err(e) = result {
+                eprintln!("mysh: {}: {}", builtin, e);
+                status = 1;
+                continue;
+            }
+        }
+        variables::set_attributes(name, |var| {
+            if let Some(exported) = attributes.exported {
+                var.exported = exported;
+            }
+            if attributes.readonly == Some(true) {
+                var.readonly = true;
+            }
+        });
+    }
+    status
+}
+
+/// `declare [-aAirxp] [name[=value] ...]`
+pub fn declare(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("declare", args, "aAirxp") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    let mut attributes = Attributes::default();
+    let mut print = false;
+    for (letter, enabled) in options {
+        match letter {
+            'i' => attributes.integer = Some(enabled),
+            'r' => attributes.readonly = Some(enabled),
+            'x' => attributes.exported = Some(enabled),
+            'a' => attributes.indexed = enabled,
+            'A' => attributes.associative = enabled,
+            _ => print = true,
+        }
+    }
+
+    if print || names.is_empty() {
+        print_declarations("declare", names, &attributes)
+    } else {
+        declare_names("declare", names, &attributes)
+    }
+}
+
+/// `export [-n] [-p] [name[=value] ...]`
+pub fn export(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("export", args, "np") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    let attributes = Attributes {
+        exported: Some(!options.contains(&('n', true))),
+        ..Default::default()
+    };
+    if options.contains(&('p', true)) || names.is_empty() {
+        print_declarations("export", &[], &attributes)
+    } else {
+        declare_names("export", names, &attributes)
+    }
+}
+
+/// `readonly [-p] [name[=value] ...]`
+pub fn readonly(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("readonly", args, "p") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    let attributes = Attributes {
+        readonly: Some(true),
+        ..Default::default()
+    };
+    if !options.is_empty() || names.is_empty() {
+        print_declarations("readonly", &[], &attributes)
+    } else {
+        declare_names("readonly", names, &attributes)
+    }
+}
+
+/// `unset [-v] [-f] name[subscript] ...`
+pub fn unset(args: &[String]) -> i32 {
+    let (options, names) = match parse_options("unset", args, "vf") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    // there are no shell functions to remove
+    if options.contains(&('f', true)) {
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in names {
+        // `unset 'name[subscript]'` removes a single element
+        let (name, subscript) = match arg.find('[') {
+            Some(n) if arg.ends_with(']') => (&arg[..n], Some(&arg[n + 1..arg.len() - 1])),
+            _ => (&arg[..], None),
+        };
+
+        let result = if !variables::is_valid_name(name) {
+            Err(format!("`{}': not a valid identifier", arg))
+        } else if let Some(subscript) = subscript {
+            variables::unset_element(name, subscript)
+        } else {
+            variables::unset(name)
+        };
+        if let Err(e) = result {
+            eprintln!("mysh: unset: {}", e);
+            status = 1;
+        }
+    }
+    status
+}
+
+#[test]
+fn test_declare() {
+    let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    assert_eq!(declare(&args(&["-ix", "TEST_DECLARE_N=1+2"])), 0);
+    let var = variables::lookup("TEST_DECLARE_N").unwrap();
+    assert_eq!(declaration("TEST_DECLARE_N", &var), "declare -ix TEST_DECLARE_N=3");
+
+    assert_eq!(readonly(&args(&["TEST_DECLARE_N"])), 0);
+    assert_eq!(declare(&args(&["TEST_DECLARE_N=4"])), 1);
+    assert_eq!(unset(&args(&["TEST_DECLARE_N"])), 1);
+    assert_eq!(variables::get("TEST_DECLARE_N"), Some("3".to_string()));
+
+    assert_eq!(export(&args(&["TEST_DECLARE_S=\"$x\""])), 0);
+    let var = variables::lookup("TEST_DECLARE_S").unwrap();
+    assert_eq!(declaration("TEST_DECLARE_S", &var), "declare -x TEST_DECLARE_S='\"$x\"'");
+    assert_eq!(export(&args(&["-n", "TEST_DECLARE_S"])), 0);
+    assert_eq!(unset(&args(&["TEST_DECLARE_S"])), 0);
+    assert_eq!(variables::lookup("TEST_DECLARE_S"), None);
+
+    assert_eq!(declare(&args(&["-a", "TEST_DECLARE_A=(x 'y z')"])), 0);
+    let var = variables::lookup("TEST_DECLARE_A").unwrap();
+    assert_eq!(declaration("TEST_DECLARE_A", &var), "declare -a TEST_DECLARE_A=([0]=x [1]='y z')");
+    assert_eq!(unset(&args(&["TEST_DECLARE_A[0]"])), 0);
+    assert_eq!(declare(&args(&["-A", "TEST_DECLARE_A"])), 1);
+
+    assert_eq!(declare(&args(&["-A", "TEST_DECLARE_M=([k]=v)"])), 0);
+    let var = variables::lookup("TEST_DECLARE_M").unwrap();
+    assert_eq!(declaration("TEST_DECLARE_M", &var), "declare -A TEST_DECLARE_M=([k]=v)");
+
+    assert_eq!(declare(&args(&["-q"])), 2);
+    assert_eq!(declare(&args(&["1x=2"])), 1);
+}
@@ -0,0 +1,211 @@
+//! `dirs`, `pushd` and `popd` builtins, managing the directory stack
+
+use super::cd;
+use crate::dirstack;
+use crate::variables;
+use std::path::Path;
+
+/// The directory with `~` in place of the home directory
+pub fn abbreviate(dir: &str) -> String {
+    match variables::get("HOME").filter(|home| !home.is_empty() && home != "/") {
+        Some(ref home) if dir == home => "~".to_string(),
+        Some(ref home) if dir.starts_with(&format!("{}/", home)) => format!("~{}", &dir[home.len()..]),
+        _ => dir.to_string(),
+    }
+}
+
+// `dirs` output: on one line, one per line with `-p`, numbered with `-v`
+fn print_stack(long: bool, per_line: bool, numbered: bool) {
+    let entries: Vec<_> = dirstack::entries()
+        .iter()
+        .map(|dir| if long { dir.clone() } else { abbreviate(dir) })
+        .collect();
+    if numbered {
+        for (i, dir) in entries.iter().enumerate() {
+            println!("{:2}  {}", i, dir);
+        }
+    } else if per_line {
+        for dir in entries {
+            println!("{}", dir);
+        }
+    } else {
+        println!("{}", entries.join(" "));
+    }
+}
+
+// index of a `+N` or `-N` argument in a stack of `len` entries, reporting it when out of range
+fn index(builtin: &str, spec: &str, len: usize) -> Option<usize> {
+    let index = dirstack::index(spec, len);
+    if index.is_none() {
+        eprintln!("mysh: {}: {}: directory stack index out of range", builtin, spec);
+    }
+    index
+}
+
+fn change_dir(dir: &str) -> bool {
+    cd::cd(&["--".to_string(), dir.to_string()]) == 0
+}
+
+// a leading `-n` asks not to change directory
+fn strip_no_cd(args: &[String]) -> (bool, &[String]) {
+    match args.split_first() {
+        Some((arg, rest)) if arg == "-n" => (true, rest),
+        _ => (false, args),
+    }
+}
+
+/// `dirs [-clpv] [+N | -N]`
+pub fn dirs(args: &[String]) -> i32 {
+    let (mut long, mut per_line, mut numbered) = (false, false, false);
+    let mut selected = None;
+    for arg in args {
+        if dirstack::is_index(arg) {
+            match index("dirs", arg, dirstack::entries().len()) {
+                Some(n) => selected = Some(n),
+                None => return 1,
+            }
+            continue;
+        }
+        if !arg.starts_with('-') || arg.len() < 2 {
+            eprintln!("mysh: dirs: {}: invalid argument", arg);
+            eprintln!("dirs: usage: dirs [-clpv] [+N] [-N]");
+            return 1;
+        }
+        for letter in arg[1..].chars() {
+            match letter {
+                'c' => {
+                    dirstack::set_saved(vec![]);
+                    return 0;
+                }
+                'l' => long = true,
+                'p' => per_line = true,
+                'v' => numbered = true,
+                _ => {
+                    eprintln!("mysh: dirs: -{}: invalid option", letter);
+                    eprintln!("dirs: usage: dirs [-clpv] [+N] [-N]");
+                    return 2;
+                }
+            }
+        }
+    }
+
+    match selected {
+        Some(n) => {
+            let dir = dirstack::entries().swap_remove(n);
+            println!("{}", if long { dir } else { abbreviate(&dir) });
+        }
+        None => print_stack(long, per_line, numbered),
+    }
+    0
+}
+
+// the stack `pushd` leaves with whether to change to its new top, or the status it fails with
+fn push(mut entries: Vec<String>, args: &[String], no_cd: bool) -> Result<(Vec<String>, bool), i32> {
+    match args {
+        [] if entries.len() < 2 => {
+            eprintln!("mysh: pushd: no other directory");
+            Err(1)
+        }
+        [] => {
+            entries.swap(0, 1);
+            Ok((entries, true))
+        }
+        // without changing directory the new top is dropped, the current one staying on top
+        [arg] if dirstack::is_index(arg) => {
+            let n = index("pushd", arg, entries.len()).ok_or(1)?;
+            entries.rotate_left(n);
+            Ok((entries, !no_cd))
+        }
+        // the directory goes below the current one, which stays on top
+        [dir] if no_cd => {
+            let dir = cd::normalize(&Path::new(&entries[0]).join(dir));
+            entries.insert(1, dir.to_string_lossy().into_owned());
+            Ok((entries, false))
+        }
+        [dir] => {
+            entries.insert(0, dir.clone());
+            Ok((entries, true))
+        }
+        _ => {
+            eprintln!("mysh: pushd: too many arguments");
+            Err(1)
+        }
+    }
+}
+
+/// `pushd [-n] [dir | +N | -N]`. Without arguments the top two directories are exchanged,
+/// an index rotates the stack to bring that entry to the top.
+pub fn pushd(args: &[String]) -> i32 {
+    let (no_cd, args) = strip_no_cd(args);
+    let (mut entries, change) = match push(dirstack::entries(), args, no_cd) {
+        Ok(pushed) => pushed,
+        Err(status) => return status,
+    };
+
+    if change && !change_dir(&entries[0]) {
+        return 1;
+    }
+    dirstack::set_saved(entries.split_off(1));
+    print_stack(false, false, false);
+    0
+}
+
+/// `popd [-n] [+N | -N]`, removing the top directory and changing to the new top,
+/// or removing the given entry
+pub fn popd(args: &[String]) -> i32 {
+    let (no_cd, args) = strip_no_cd(args);
+    let mut entries = dirstack::entries();
+    let n = match args {
+        [] => 0,
+        [arg] if dirstack::is_index(arg) => match index("popd", arg, entries.len()) {
+            Some(n) => n,
+            None => return 1,
+        },
+        [arg] => {
+            eprintln!("mysh: popd: {}: invalid argument", arg);
+            eprintln!("popd: usage: popd [-n] [+N | -N]");
+            return 2;
+        }
+        _ => {
+            eprintln!("mysh: popd: too many arguments");
+            return 1;
+        }
+    };
+    if entries.len() < 2 {
+        eprintln!("mysh: popd: directory stack empty");
+        return 1;
+    }
+
+    match n {
+        0 if no_cd => {
+            entries.remove(1);
+        }
+        0 => {
+            if !change_dir(&entries[1]) {
+                return 1;
+            }
+            entries.remove(0);
+        }
+        n => {
+            entries.remove(n);
+        }
+    }
+    dirstack::set_saved(entries.split_off(1));
+    print_stack(false, false, false);
+    0
+}
+
+#[test]
+fn test_push() {
+    let stack = |dirs: &[&str]| dirs.iter().map(|dir| dir.to_string()).collect::<Vec<_>>();
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let entries = stack(&["/a", "/b", "/c"]);
+
+    assert_eq!(push(entries.clone(), &args(&["+1"]), false), Ok((stack(&["/b", "/c", "/a"]), true)));
+    // `-n` rotates the stack without changing directory
+    assert_eq!(push(entries.clone(), &args(&["-0"]), true), Ok((stack(&["/c", "/a", "/b"]), false)));
+    assert_eq!(push(entries.clone(), &args(&["+3"]), true), Err(1));
+    // and stores the directory as an absolute path
+    assert_eq!(push(entries.clone(), &args(&["d/../e"]), true), Ok((stack(&["/a", "/a/e", "/b", "/c"]), false)));
+    assert_eq!(push(entries, &args(&["/f"]), true), Ok((stack(&["/a", "/f", "/b", "/c"]), false)));
+}
@@ -0,0 +1,38 @@
+//! `exit` builtin, leaving the shell
+
+use crate::interpret;
+use crate::jobs::{self, State};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set once the user has been told about stopped jobs, the next `exit` going ahead
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// `exit [n]`, with the status of the last command when `n` is left out.
+/// An interactive shell with stopped jobs warns about them once instead of exiting.
+pub fn exit(args: &[String]) -> i32 {
+    let status = match args {
+        [] => interpret::last_status(),
+        [n] => match n.parse::<i64>() {
+            Ok(n) => (n & 0xff) as i32,
+            Err(_) => {
+                eprintln!("mysh: exit: {}: numeric argument required", n);
+                2
+            }
+        },
+        _ => {
+            eprintln!("mysh: exit: too many arguments");
+            return 1;
+        }
+    };
+
+    if jobs::is_enabled() {
+        eprintln!("exit");
+        jobs::update();
+        let stopped = jobs::list().iter().any(|(job, _)| matches!(job.state(), State::Stopped(_)));
+        if stopped && !WARNED.swap(true, Ordering::SeqCst) {
+            eprintln!("There are stopped jobs.");
+            return 1;
+        }
+    }
+    interpret::exit_shell(status)
+}
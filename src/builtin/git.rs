@@ -0,0 +1,329 @@
+//! Git segment of the prompt, `\g`, read straight from the repository files.
+//!
+//! Shows the branch or the detached commit, an operation in progress, `*` when
+//! tracked files differ from the index and `=`, `>` or `<` for whether the
+//! branch is even with, ahead of or behind its upstream. Commits are compressed
+//! objects, so the upstream relation is worked out from the reflogs rather than
+//! by walking history, and left out when they don't settle it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Longest the prompt waits for the segment, a slow filesystem leaving it out
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Set while a worker reads a repository, possibly one a prompt stopped waiting for
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Where a repository keeps its files
+struct Repository {
+    /// Directory checked out, holding `.git`
+    work_tree: PathBuf,
+    /// `HEAD` and `index` of this work tree
+    git_dir: PathBuf,
+    /// Refs, logs and config, shared between linked work trees
+    common_dir: PathBuf,
+}
+
+impl Repository {
+    // the repository containing the directory, found through the `.git` of it or an ancestor
+    fn discover(dir: &Path) -> Option<Repository> {
+        for work_tree in dir.ancestors() {
+            let dot_git = work_tree.join(".git");
+            let git_dir = if dot_git.is_dir() {
+                dot_git
+            } else if dot_git.is_file() {
+                // linked work trees and submodules point to their git directory
+                let contents = fs::read_to_string(&dot_git).ok()?;
+                work_tree.join(contents.trim().strip_prefix("gitdir:")?.trim())
+            } else {
+                continue;
+            };
+            let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+                Ok(common) => git_dir.join(common.trim()),
+                Err(_) => git_dir.clone(),
+            };
+            return Some(Repository {
+                work_tree: work_tree.to_path_buf(),
+                git_dir,
+                common_dir,
+            });
+        }
+        None
+    }
+
+    // commit a ref points to, following symbolic refs
+    fn resolve(&self, name: &str) -> Option<String> {
+        let mut name = name.to_string();
+        for _ in 0..5 {
+            let dir = if name == "HEAD" { &self.git_dir } else { &self.common_dir };
+            let contents = match fs::read_to_string(dir.join(&name)) {
+                Ok(contents) => contents,
+                Err(_) => return self.packed_ref(&name),
+            };
+            match contents.trim().strip_prefix("ref:") {
+                Some(target) => name = target.trim().to_string(),
+                None => return Some(contents.trim().to_string()),
+            }
+        }
+        None
+    }
+
+    fn packed_ref(&self, name: &str) -> Option<String> {
+        let packed = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        packed
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| line.split_once(' '))
+            .find(|&(_, ref_name)| ref_name == name)
+            .map(|(commit, _)| commit.to_string())
+    }
+
+    // whether the commit was ever the tip of the ref, as its reflog records
+    fn was_at(&self, name: &str, commit: &str) -> bool {
+        let log = fs::read_to_string(self.common_dir.join("logs").join(name)).unwrap_or_default();
+        log.lines().any(|line| line.split(' ').take(2).any(|logged| logged == commit))
+    }
+
+    // `remote` and `merge` of the `[branch "name"]` config section
+    fn upstream(&self, branch: &str) -> Option<String> {
+        let config = fs::read_to_string(self.common_dir.join("config")).ok()?;
+        let section = format!("[branch \"{}\"]", branch);
+        let (mut remote, mut merge) = (None, None);
+        let mut in_section = false;
+        for line in config.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_section = line == section;
+            } else if let (true, Some((key, value))) = (in_section, line.split_once('=')) {
+                match &key.trim().to_ascii_lowercase()[..] {
+                    "remote" => remote = Some(value.trim().to_string()),
+                    "merge" => merge = Some(value.trim().to_string()),
+                    _ => (),
+                }
+            }
+        }
+        let merge = merge?;
+        match remote?.as_str() {
+            "." => Some(merge),
+            remote => Some(format!("refs/remotes/{}/{}", remote, merge.strip_prefix("refs/heads/")?)),
+        }
+    }
+
+    // `|MERGING` and the like while an operation waits to be finished
+    fn operation(&self) -> &'static str {
+        let states = [
+            ("rebase-merge", "|REBASE"),
+            ("rebase-apply", "|REBASE"),
+            ("MERGE_HEAD", "|MERGING"),
+            ("CHERRY_PICK_HEAD", "|CHERRY-PICKING"),
+            ("REVERT_HEAD", "|REVERTING"),
+            ("BISECT_LOG", "|BISECTING"),
+        ];
+        states
+            .iter()
+            .find(|(file, _)| self.git_dir.join(file).exists())
+            .map_or("", |&(_, state)| state)
+    }
+
+    // whether a tracked file's size or modification time differs from what the index records
+    fn is_dirty(&self) -> bool {
+        let index = match fs::read(self.git_dir.join("index")) {
+            Ok(index) => index,
+            Err(_) => return false,
+        };
+        index_entries(&index).unwrap_or_default().iter().any(|entry| {
+            let metadata = match fs::symlink_metadata(self.work_tree.join(&entry.path)) {
+                Ok(metadata) => metadata,
+                Err(_) => return true,
+            };
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |mtime| mtime.as_secs() as u32);
+            metadata.len() as u32 != entry.size || mtime != entry.mtime
+        })
+    }
+}
+
+/// What the index records of a tracked file
+#[derive(Debug, PartialEq)]
+struct IndexEntry {
+    path: String,
+    mtime: u32,
+    size: u32,
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    let bytes = bytes.get(at..at + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// entries of a version 2, 3 or 4 index file, submodules and skip-worktree entries left out
+fn index_entries(index: &[u8]) -> Option<Vec<IndexEntry>> {
+    if index.get(..4)? != b"DIRC" {
+        return None;
+    }
+    let version = read_u32(index, 4)?;
+    let count = read_u32(index, 8)?;
+    let mut entries = vec![];
+    let mut path = Vec::new();
+    let mut at = 12;
+
+    for _ in 0..count {
+        let start = at;
+        let mtime = read_u32(index, at + 8)?;
+        let mode = read_u32(index, at + 24)?;
+        let size = read_u32(index, at + 36)?;
+        let flags = u16::from_be_bytes([*index.get(at + 60)?, *index.get(at + 61)?]);
+        at += 62;
+        let mut skip_worktree = false;
+        if version >= 3 && flags & 0x4000 != 0 {
+            skip_worktree = index.get(at)? & 0x40 != 0;
+            at += 2;
+        }
+
+        if version >= 4 {
+            // the path drops a number of bytes from the end of the previous one
+            let mut strip = 0usize;
+            loop {
+                let byte = *index.get(at)?;
+                at += 1;
+                strip = (strip << 7) | (byte & 0x7f) as usize;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                strip += 1;
+            }
+            path.truncate(path.len().checked_sub(strip)?);
+            let end = at + index.get(at..)?.iter().position(|&byte| byte == 0)?;
+            path.extend_from_slice(&index[at..end]);
+            at = end + 1;
+        } else {
+            let end = at + index.get(at..)?.iter().position(|&byte| byte == 0)?;
+            path = index[at..end].to_vec();
+            // entries are padded with NULs to a multiple of eight bytes
+            at = start + (end - start + 8) / 8 * 8;
+        }
+
+        if mode & 0o170000 != 0o160000 && !skip_worktree {
+            let path = String::from_utf8_lossy(&path).into_owned();
+            entries.push(IndexEntry { path, mtime, size });
+        }
+    }
+    Some(entries)
+}
+
+// the segment for the repository containing the directory, empty outside one
+fn segment(dir: &Path) -> String {
+    let repo = match Repository::discover(dir) {
+        Some(repo) => repo,
+        None => return String::new(),
+    };
+    let head = fs::read_to_string(repo.git_dir.join("HEAD")).unwrap_or_default();
+    let branch = head.trim().strip_prefix("ref:").map(|target| target.trim().to_string());
+
+    let name = match &branch {
+        Some(target) => target.strip_prefix("refs/heads/").unwrap_or(target).to_string(),
+        None => format!("({}...)", head.trim().get(..7).unwrap_or("unknown")),
+    };
+    let mut segment = format!("{}{}", name, repo.operation());
+
+    let mut markers = String::new();
+    if repo.is_dirty() {
+        markers.push('*');
+    }
+    if let Some(target) = &branch {
+        let upstream = repo.upstream(&name).and_then(|upstream| repo.resolve(&upstream).map(|commit| (upstream, commit)));
+        if let (Some(commit), Some((upstream, upstream_commit))) = (repo.resolve(target), upstream) {
+            if commit == upstream_commit {
+                markers.push('=');
+            } else if repo.was_at(target, &upstream_commit) {
+                markers.push('>');
+            } else if repo.was_at(&upstream, &commit) {
+                markers.push('<');
+            }
+        }
+    }
+    if !markers.is_empty() {
+        segment.push(' ');
+        segment.push_str(&markers);
+    }
+    segment
+}
+
+/// Git segment for the directory, left empty when it takes longer than the timeout.
+/// While a worker that timed out is still running no other one is started,
+/// so slow repositories don't pile up threads, and the segment stays empty.
+pub fn status(dir: &Path) -> String {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return String::new();
+    }
+    let dir = dir.to_path_buf();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let segment = segment(&dir);
+        RUNNING.store(false, Ordering::SeqCst);
+        sender.send(segment)
+    });
+    receiver.recv_timeout(TIMEOUT).unwrap_or_default()
+}
+
+#[test]
+fn test_segment() {
+    let dir = std::env::temp_dir().join(format!("mysh-test-git-{}", std::process::id()));
+    let git_dir = dir.join(".git");
+    fs::create_dir_all(git_dir.join("logs/refs/heads")).unwrap();
+    fs::create_dir_all(dir.join("src")).unwrap();
+    let write = |name: &str, contents: &str| fs::write(git_dir.join(name), contents).unwrap();
+    write("HEAD", "ref: refs/heads/topic\n");
+    write("packed-refs", "# pack-refs\naaaaaaaa refs/heads/topic\nbbbbbbbb refs/remotes/origin/topic\n");
+    write("config", "[core]\n\tbare = false\n[branch \"topic\"]\n\tremote = origin\n\tmerge = refs/heads/topic\n");
+
+    assert_eq!(segment(&dir.join("src")), "topic");
+    write("logs/refs/heads/topic", "bbbbbbbb aaaaaaaa A <a@b> 0 +0000\tcommit: x\n");
+    assert_eq!(segment(&dir), "topic >");
+    write("MERGE_HEAD", "cccccccc\n");
+    write("HEAD", "aaaaaaaabbbb\n");
+    assert_eq!(segment(&dir), "(aaaaaaa...)|MERGING");
+    fs::remove_dir_all(&dir).unwrap();
+
+    let outside = std::env::temp_dir().join(format!("mysh-test-nogit-{}", std::process::id()));
+    fs::create_dir(&outside).unwrap();
+    if outside.ancestors().all(|dir| !dir.join(".git").exists()) {
+        assert_eq!(segment(&outside), "");
+    }
+    fs::remove_dir(&outside).unwrap();
+}
+
+#[test]
+fn test_status_single_worker() {
+    let repo = Path::new(env!("CARGO_MANIFEST_DIR"));
+    RUNNING.store(true, Ordering::SeqCst);
+    assert_eq!(status(repo), "");
+    RUNNING.store(false, Ordering::SeqCst);
+    // a segment that arrived in time leaves no worker running
+    if !status(repo).is_empty() {
+        assert!(!RUNNING.load(Ordering::SeqCst));
+    }
+}
+
+#[test]
+fn test_index_entries() {
+    let mut index = b"DIRC\0\0\0\x02\0\0\0\x01".to_vec();
+    let mut entry = vec![0u8; 62];
+    entry[8..12].copy_from_slice(&7u32.to_be_bytes());
+    entry[24..28].copy_from_slice(&0o100644u32.to_be_bytes());
+    entry[36..40].copy_from_slice(&5u32.to_be_bytes());
+    entry[60..62].copy_from_slice(&4u16.to_be_bytes());
+    entry.extend_from_slice(b"a.rs\0\0\0\0\0\0");
+    index.extend(entry);
+
+    let expected = IndexEntry { path: "a.rs".to_string(), mtime: 7, size: 5 };
+    assert_eq!(index_entries(&index), Some(vec![expected]));
+    assert_eq!(index_entries(b"JUNK"), None);
+}
@@ -0,0 +1,186 @@
+//! Job control builtins: `jobs`, `fg`, `bg`, `wait` and `disown`
+
+use super::declare::parse_options;
+use crate::jobs;
+use nix::unistd::Pid;
+
+/// `jobs [-lp] [jobspec ...]`
+pub fn jobs(args: &[String]) -> i32 {
+    let (options, specs) = match parse_options("jobs", args, "lp") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    jobs::update();
+    let mut status = 0;
+    let mut listing = jobs::list();
+    if !specs.is_empty() {
+        let mut ids = vec![];
+        for spec in specs {
+            match jobs::find(spec) {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    eprintln!("mysh: jobs: {}", e);
+                    status = 1;
+                }
+            }
+        }
+        listing.retain(|(job, _)| ids.contains(&job.id));
+    }
+
+    for (job, marker) in listing {
+        if options.contains(&('p', true)) {
+            if let Some(pid) = job.leader() {
+                println!("{}", pid);
+            }
+        } else {
+            println!("{}", jobs::format(&job, marker, options.contains(&('l', true))));
+        }
+        // finished jobs are only reported once
+        if job.state().is_done() {
+            jobs::retire(job.id);
+        }
+    }
+    status
+}
+
+// job number named by the spec, the current job if there is none
+fn job_id(builtin: &str, spec: Option<&String>) -> Result<usize, i32> {
+    if !jobs::is_enabled() {
+        eprintln!("mysh: {}: no job control", builtin);
+        return Err(1);
+    }
+    jobs::find(spec.map_or("%+", |spec| &spec[..])).map_err(|e| {
+        eprintln!("mysh: {}: {}", builtin, e);
+        1
+    })
+}
+
+/// `fg [jobspec]`
+pub fn fg(args: &[String]) -> i32 {
+    let id = match job_id("fg", args.first()) {
+        Ok(id) => id,
+        Err(status) => return status,
+    };
+    jobs::continue_job(id, true).unwrap_or_else(|e| {
+        eprintln!("mysh: fg: {}", e);
+        1
+    })
+}
+
+/// `bg [jobspec ...]`
+pub fn bg(args: &[String]) -> i32 {
+    let specs: Vec<_> = if args.is_empty() { vec![None] } else { args.iter().map(Some).collect() };
+
+    let mut status = 0;
+    for spec in specs {
+        let result = job_id("bg", spec).and_then(|id| {
+            jobs::continue_job(id, false).map_err(|e| {
+                eprintln!("mysh: bg: {}", e);
+                1
+            })
+        });
+        if let Err(e) = result {
+            status = e;
+        }
+    }
+    status
+}
+
+// exit status of the job or process named by a `wait` operand
+fn wait_for(spec: &str) -> i32 {
+    if spec.starts_with('%') {
+        return match jobs::find(spec) {
+            Ok(id) => jobs::wait_job(id, None).unwrap_or(127),
+            Err(e) => {
+                eprintln!("mysh: wait: {}", e);
+                127
+            }
+        };
+    }
+
+    let pid = match spec.parse() {
+        Ok(pid) => Pid::from_raw(pid),
+        Err(_) => {
+            eprintln!("mysh: wait: `{}': not a pid or valid job spec", spec);
+            return 2;
+        }
+    };
+    match jobs::find_pid(pid) {
+        Some(id) => jobs::wait_job(id, Some(pid)).unwrap_or(127),
+        // a job reported as done has left the table, its status hasn't
+        None => jobs::take_reaped(pid).unwrap_or_else(|| {
+            eprintln!("mysh: wait: pid {} is not a child of this shell", pid);
+            127
+        }),
+    }
+}
+
+/// `wait [-n] [pid|jobspec ...]`
+pub fn wait(args: &[String]) -> i32 {
+    let (options, specs) = match parse_options("wait", args, "n") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+
+    if options.contains(&('n', true)) {
+        return jobs::wait_any().unwrap_or(127);
+    }
+    if specs.is_empty() {
+        for (job, _) in jobs::list() {
+            jobs::wait_job(job.id, None);
+        }
+        return 0;
+    }
+    // the status of the last operand is the status of `wait`
+    specs.iter().fold(0, |_, spec| wait_for(spec))
+}
+
+/// `disown [-ahr] [jobspec ...]`, removing jobs from the table so the shell
+/// neither reports them nor hangs up on them, or with `-h` only sparing them the hangup
+pub fn disown(args: &[String]) -> i32 {
+    let (options, specs) = match parse_options("disown", args, "ahr") {
+        Ok(parsed) => parsed,
+        Err(status) => return status,
+    };
+    let enabled = |letter| options.contains(&(letter, true));
+
+    jobs::update();
+    let mut status = 0;
+    let ids: Vec<_> = if !specs.is_empty() {
+        specs
+            .iter()
+            .filter_map(|spec| {
+                jobs::find(spec)
+                    .map_err(|e| {
+                        eprintln!("mysh: disown: {}", e);
+                        status = 1;
+                    })
+                    .ok()
+            })
+            .collect()
+    } else if enabled('a') || enabled('r') {
+        let listing = jobs::list().into_iter().map(|(job, _)| job);
+        listing
+            .filter(|job| !enabled('r') || job.state() == jobs::State::Running)
+            .map(|job| job.id)
+            .collect()
+    } else {
+        match jobs::find("%+") {
+            Ok(id) => vec![id],
+            Err(e) => {
+                eprintln!("mysh: disown: {}", e);
+                return 1;
+            }
+        }
+    };
+
+    for id in ids {
+        if enabled('h') {
+            jobs::set_nohup(id);
+        } else {
+            jobs::disown(id);
+        }
+    }
+    status
+}
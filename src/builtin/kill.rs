@@ -0,0 +1,135 @@
+//! `kill` builtin, sending signals to processes and jobs
+
+use crate::jobs;
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+
+/// Signal with the given number, or name where both the `SIG` prefix and case are optional
+pub fn parse_signal(spec: &str) -> Option<Signal> {
+    if let Ok(number) = spec.parse::<i32>() {
+        return Signal::iterator().find(|&signal| signal as i32 == number);
+    }
+    let name = spec.to_ascii_uppercase();
+    let name = if name.starts_with("SIG") { name } else { format!("SIG{}", name) };
+    name.parse().ok()
+}
+
+/// Name of the signal without the `SIG` prefix
+pub fn signal_name(signal: Signal) -> String {
+    signal.as_ref()[3..].to_string()
+}
+
+fn describe(e: nix::Error) -> String {
+    match e {
+        nix::Error::Sys(errno) => errno.desc().to_string(),
+        e => e.to_string(),
+    }
+}
+
+// `kill -l`, converting between signal names and numbers
+fn list_signals(args: &[String]) -> i32 {
+    if args.is_empty() {
+        let signals: Vec<_> = Signal::iterator()
+            .map(|signal| format!("{:2}) {}", signal as i32, signal.as_ref()))
+            .collect();
+        for row in signals.chunks(5) {
+            println!("{}", row.join("\t"));
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let converted = match arg.parse::<i32>() {
+            // the exit status of a killed process names its signal too
+            Ok(number) if number > 128 => parse_signal(&(number - 128).to_string()).map(signal_name),
+            Ok(_) => parse_signal(arg).map(signal_name),
+            Err(_) => parse_signal(arg).map(|signal| (signal as i32).to_string()),
+        };
+        match converted {
+            Some(converted) => println!("{}", converted),
+            None => {
+                eprintln!("mysh: kill: {}: invalid signal specification", arg);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// `kill [-s sigspec | -n signum | -sigspec] pid | jobspec ...` or `kill -l [sigspec]`
+pub fn kill(args: &[String]) -> i32 {
+    // signal 0 only checks whether the process exists
+    let parse = |spec: &str| match spec {
+        "0" => Ok(None),
+        _ => parse_signal(spec).map(Some).ok_or_else(|| {
+            eprintln!("mysh: kill: {}: invalid signal specification", spec);
+            1
+        }),
+    };
+
+    let mut signal = Some(Signal::SIGTERM);
+    let mut targets = args;
+    let result = match args.first().map(|arg| &arg[..]) {
+        Some("-l") | Some("-L") => return list_signals(&args[1..]),
+        Some(option @ "-s") | Some(option @ "-n") => match args.get(1) {
+            Some(spec) => {
+                targets = &args[2..];
+                parse(spec)
+            }
+            None => {
+                eprintln!("mysh: kill: {}: option requires an argument", option);
+                return 2;
+            }
+        },
+        Some("--") => {
+            targets = &args[1..];
+            Ok(signal)
+        }
+        Some(arg) if arg.starts_with('-') && arg.len() > 1 => {
+            targets = &args[1..];
+            parse(&arg[1..])
+        }
+        _ => Ok(signal),
+    };
+    match result {
+        Ok(parsed) => signal = parsed,
+        Err(status) => return status,
+    }
+    if targets.first().is_some_and(|arg| arg == "--") {
+        targets = &targets[1..];
+    }
+    if targets.is_empty() {
+        eprintln!("kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... or kill -l [sigspec]");
+        return 2;
+    }
+
+    let mut status = 0;
+    for target in targets {
+        let result = if target.starts_with('%') {
+            jobs::find(target).and_then(|id| jobs::signal_job(id, signal).map_err(describe))
+        } else {
+            match target.parse() {
+                // negative pids name process groups
+                Ok(pid) => signal::kill(Pid::from_raw(pid), signal)
+                    .map_err(|e| format!("({}) - {}", pid, describe(e))),
+                Err(_) => Err(format!("{}: arguments must be process or job IDs", target)),
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("mysh: kill: {}", e);
+            status = 1;
+        }
+    }
+    status
+}
+
+#[test]
+fn test_parse_signal() {
+    assert_eq!(parse_signal("9"), Some(Signal::SIGKILL));
+    assert_eq!(parse_signal("term"), Some(Signal::SIGTERM));
+    assert_eq!(parse_signal("SIGHUP"), Some(Signal::SIGHUP));
+    assert_eq!(parse_signal("FOO"), None);
+    assert_eq!(parse_signal("200"), None);
+    assert_eq!(signal_name(Signal::SIGINT), "INT");
+}
@@ -0,0 +1,61 @@
+mod alias;
+pub mod cd;
+mod command;
+mod declare;
+mod dirs;
+mod exit;
+mod git;
+mod job_control;
+mod kill;
+mod prompt;
+mod set;
+pub mod source;
+mod trap;
+
+pub use self::exit::exit;
+pub use self::prompt::{erase_right_prompt, get_continuation_prompt, get_prompt, set_last_duration};
+
+/// Builtins receive their arguments without the command name and return the exit status
+pub type BuiltinFn = fn(&[String]) -> i32;
+
+const BUILTINS: &[(&str, BuiltinFn)] = &[
+    (".", source::source),
+    ("alias", alias::alias),
+    ("bg", job_control::bg),
+    ("cd", cd::cd),
+    ("command", command::command),
+    ("declare", declare::declare),
+    ("dirs", dirs::dirs),
+    ("disown", job_control::disown),
+    ("exit", exit::exit),
+    ("export", declare::export),
+    ("fg", job_control::fg),
+    ("hash", command::hash),
+    ("jobs", job_control::jobs),
+    ("kill", kill::kill),
+    ("popd", dirs::popd),
+    ("prompt", prompt::prompt),
+    ("pushd", dirs::pushd),
+    ("pwd", cd::pwd),
+    ("readonly", declare::readonly),
+    ("set", set::set),
+    ("shift", set::shift),
+    ("source", source::source),
+    ("trap", trap::trap),
+    ("type", command::r#type),
+    ("unalias", alias::unalias),
+    ("unset", declare::unset),
+    ("wait", job_control::wait),
+];
+
+pub fn find_builtin(name: &str) -> Option<BuiltinFn> {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, f)| *f)
+}
+
+/// Names of every builtin, sorted
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|(name, _)| *name)
+}
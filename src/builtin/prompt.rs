@@ -0,0 +1,335 @@
+//! `prompt` builtin and the rendering of `PS1`, expanded afresh before each command is read
+
+use super::dirs::abbreviate;
+use super::git;
+use crate::expand;
+use crate::interpret;
+use crate::jobs;
+use crate::quote::quote;
+use crate::variables;
+use nix::libc;
+use nix::unistd;
+use std::ffi::CString;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Prompt shown when `PS1` is not set
+const DEFAULT_PROMPT: &str = "λ ";
+
+/// Wall-clock time the last command took, in microseconds
+static LAST_DURATION: AtomicU64 = AtomicU64::new(0);
+
+/// Column where typed input starts, after the last line of `PS1`
+static INPUT_COLUMN: AtomicUsize = AtomicUsize::new(0);
+
+/// Column where `RPS1` was drawn, zero when it wasn't
+static RIGHT_COLUMN: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks around the non-printing sequences of a rendered prompt, as readline does
+const START_IGNORE: char = '\x01';
+const END_IGNORE: char = '\x02';
+
+/// Records how long the last command took, for `\C`
+pub fn set_last_duration(duration: Duration) {
+    LAST_DURATION.store(duration.as_micros() as u64, Ordering::SeqCst);
+}
+
+// `12ms`, `3.4s`, `2m05s` or `1h02m`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
+    }
+}
+
+// formats the local time with strftime(3)
+fn strftime(format: &str) -> String {
+    let format = match CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+    let mut buffer = [0u8; 256];
+    let len = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        libc::strftime(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len(), format.as_ptr(), &tm)
+    };
+    String::from_utf8_lossy(&buffer[..len]).into_owned()
+}
+
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    unistd::gethostname(&mut buffer)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+// login name of the user the shell runs as, from the password database
+fn user_name() -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 1024];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    loop {
+        let uid = unistd::getuid().into();
+        let error = unsafe { libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+        if error != libc::ERANGE {
+            break;
+        }
+        let len = buffer.len() * 2;
+        buffer.resize(len, 0);
+    }
+    if result.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+fn user() -> String {
+    user_name()
+        .or_else(|| variables::get("USER"))
+        .or_else(|| variables::get("LOGNAME"))
+        .unwrap_or_else(|| unistd::getuid().to_string())
+}
+
+fn working_dir() -> String {
+    variables::get("PWD").unwrap_or_else(|| std::env::current_dir().unwrap_or_default().to_string_lossy().into_owned())
+}
+
+// value of a single escape, `None` for an unknown one which is kept as typed
+fn escape(letter: char, argument: Option<&str>) -> Option<String> {
+    let value = match letter {
+        'u' => user(),
+        'h' => hostname().split('.').next().unwrap_or_default().to_string(),
+        'H' => hostname(),
+        'w' => abbreviate(&working_dir()),
+        'W' => match abbreviate(&working_dir()) {
+            ref dir if dir == "~" || dir == "/" => dir.clone(),
+            dir => dir.rsplit('/').next().unwrap_or_default().to_string(),
+        },
+        '$' => if unistd::geteuid().is_root() { "#" } else { "$" }.to_string(),
+        't' => strftime("%H:%M:%S"),
+        'T' => strftime("%I:%M:%S"),
+        '@' => strftime("%I:%M %p"),
+        'A' => strftime("%H:%M"),
+        'd' => strftime("%a %b %d"),
+        'D' => strftime(match argument {
+            Some("") | None => "%X",
+            Some(format) => format,
+        }),
+        'g' => git::status(Path::new(&working_dir())),
+        'j' => jobs::list().len().to_string(),
+        '?' => interpret::last_status().to_string(),
+        'X' => match interpret::last_status() {
+            0 => String::new(),
+            status => status.to_string(),
+        },
+        'C' => format_duration(Duration::from_micros(LAST_DURATION.load(Ordering::SeqCst))),
+        's' => {
+            let arg0 = variables::arg0();
+            arg0.rsplit('/').next().unwrap_or_default().to_string()
+        }
+        'v' | 'V' => env!("CARGO_PKG_VERSION").to_string(),
+        'n' => "\n".to_string(),
+        'r' => "\r".to_string(),
+        'a' => "\x07".to_string(),
+        'e' => "\x1b".to_string(),
+        '\\' => "\\".to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+// replaces the backslash escapes of a prompt string, non-printing sequences
+// between `\[` and `\]` being marked with START_IGNORE and END_IGNORE
+fn render_escapes(template: &str) -> String {
+    let mut rendered = String::new();
+    let mut it = template.chars().peekable();
+    while let Some(ch) = it.next() {
+        if ch != '\\' {
+            rendered.push(ch);
+            continue;
+        }
+        let letter = match it.next() {
+            Some(letter) => letter,
+            None => {
+                rendered.push('\\');
+                break;
+            }
+        };
+        match letter {
+            '[' => rendered.push(START_IGNORE),
+            ']' => rendered.push(END_IGNORE),
+            // `\nnn` is the character with that octal code
+            '0'..='7' => {
+                let mut code = letter.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match it.peek().and_then(|ch| ch.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            it.next();
+                        }
+                        None => break,
+                    }
+                }
+                rendered.extend(std::char::from_u32(code));
+            }
+            'D' if it.peek() == Some(&'{') => {
+                let format: String = it.by_ref().skip(1).take_while(|&ch| ch != '}').collect();
+                rendered.push_str(&quote(&escape('D', Some(&format)).unwrap()));
+            }
+            letter => match escape(letter, None) {
+                // the values are quoted so that the expansion that follows leaves them alone
+                Some(value) => rendered.push_str(&quote(&value)),
+                None => {
+                    rendered.push('\\');
+                    rendered.push(letter);
+                }
+            },
+        }
+    }
+    rendered
+}
+
+/// Uses the string as `PS1`
+pub fn set_prompt(prompt_str: &str) {
+    if let Err(e) = variables::set("PS1", prompt_str) {
+        eprintln!("mysh: prompt: {}", e);
+    }
+}
+
+// escapes replaced, then parameters expanded
+fn render(template: &str) -> String {
+    let rendered = render_escapes(template);
+    expand::expand_string(&rendered).unwrap_or(rendered)
+}
+
+// columns taken on screen by the last line of a rendered prompt
+fn visible_width(rendered: &str) -> usize {
+    let last_line = rendered.rsplit('\n').next().unwrap_or_default();
+    let mut ignoring = false;
+    last_line
+        .chars()
+        .filter(|&ch| match ch {
+            START_IGNORE => {
+                ignoring = true;
+                false
+            }
+            END_IGNORE => {
+                ignoring = false;
+                false
+            }
+            _ => !ignoring,
+        })
+        .count()
+}
+
+fn strip_markers(rendered: &str) -> String {
+    rendered.chars().filter(|&ch| ch != START_IGNORE && ch != END_IGNORE).collect()
+}
+
+// columns of the terminal, from TIOCGWINSZ on standard output
+fn terminal_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_col > 0 => Some(size.ws_col as usize),
+        _ => None,
+    }
+}
+
+/// `PS1` as shown before reading a command, followed by `RPS1` drawn against the
+/// right edge of the terminal when both fit on the line
+pub fn get_prompt() -> String {
+    let left = render(&variables::get("PS1").unwrap_or_else(|| DEFAULT_PROMPT.to_string()));
+    let input_column = visible_width(&left);
+    let mut prompt = strip_markers(&left);
+    RIGHT_COLUMN.store(0, Ordering::SeqCst);
+
+    let right = match variables::get("RPS1").filter(|template| !template.is_empty()) {
+        Some(template) => render(&template),
+        None => return prompt,
+    };
+    let width = visible_width(&right);
+    match terminal_width() {
+        Some(columns) if !right.contains('\n') && input_column + width < columns => {
+            let column = columns - width;
+            // saves the cursor, draws at the column and goes back to where input starts
+            prompt.push_str(&format!("\x1b7\x1b[{}G{}\x1b8", column + 1, strip_markers(&right)));
+            INPUT_COLUMN.store(input_column, Ordering::SeqCst);
+            RIGHT_COLUMN.store(column, Ordering::SeqCst);
+        }
+        _ => (),
+    }
+    prompt
+}
+
+/// Clears what typed input left of `RPS1` once the line it ran into has been entered.
+/// The terminal echoes input itself, so the right prompt is only overwritten while typing.
+pub fn erase_right_prompt(line: &str) {
+    let right_column = RIGHT_COLUMN.swap(0, Ordering::SeqCst);
+    if right_column == 0 {
+        return;
+    }
+    let end = INPUT_COLUMN.load(Ordering::SeqCst) + line.trim_end_matches('\n').chars().count();
+    if end >= right_column && end < terminal_width().unwrap_or(0) {
+        // back up to the line just entered, clear past the input and come down again
+        print!("\x1b[1A\x1b[{}G\x1b[K\x1b[1B\r", end + 1);
+        io::stdout().flush().ok();
+    }
+}
+
+/// Prompt shown while reading the rest of an incomplete command
+pub fn get_continuation_prompt() -> String {
+    strip_markers(&render(&variables::get("PS2").unwrap_or_else(|| "> ".to_string())))
+}
+
+/// `prompt [string ...]`, printing `PS1` or setting it to the strings joined by spaces
+pub fn prompt(args: &[String]) -> i32 {
+    if args.is_empty() {
+        println!("{}", variables::get("PS1").unwrap_or_else(|| DEFAULT_PROMPT.to_string()));
+        return 0;
+    }
+    set_prompt(&args.join(" "));
+    0
+}
+
+#[test]
+fn test_prompt() {
+    set_prompt("λ ");
+    assert!(get_prompt() == "λ ");
+
+    set_prompt("% ");
+    assert!(get_prompt() == "% ");
+}
+
+#[test]
+fn test_render_escapes() {
+    assert_eq!(render_escapes("\\[\\e[1m\\]x"), "\x01'\x1b'[1m\x02x");
+    assert_eq!(render_escapes("a\\101\\\\b\\q"), "aA'\\'b\\q");
+    assert_eq!(render("a\\101\\\\b"), "aA\\b");
+    assert_eq!(render_escapes("\\D{%%}"), "%");
+}
+
+#[test]
+fn test_visible_width() {
+    let rendered = render("\\u\\n\\[\\e[32m\\]ab\\[\\e[0m\\] ");
+    assert_eq!(visible_width(&rendered), 3);
+    assert_eq!(strip_markers(&rendered).matches('\x1b').count(), 2);
+}
+
+#[test]
+fn test_format_duration() {
+    assert_eq!(format_duration(Duration::from_micros(12_500)), "12ms");
+    assert_eq!(format_duration(Duration::from_millis(3_450)), "3.5s");
+    assert_eq!(format_duration(Duration::from_secs(125)), "2m05s");
+    assert_eq!(format_duration(Duration::from_secs(3_720)), "1h02m");
+}
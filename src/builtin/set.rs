@@ -0,0 +1,84 @@
+//! `set` and `shift` builtins, changing shell options and positional parameters
+
+use super::declare::format_value;
+use crate::options;
+use crate::variables;
+
+// `set -o` lists the options, `set +o` prints the commands recreating them
+fn print_options(as_commands: bool) {
+    for (name, enabled) in options::all() {
+        if as_commands {
+            println!("set {}o {}", if enabled { '-' } else { '+' }, name);
+        } else {
+            println!("{:<15}\t{}", name, if enabled { "on" } else { "off" });
+        }
+    }
+}
+
+/// `set [-efuvx] [+efuvx] [-o option-name] [+o option-name] [--] [arg ...]`
+pub fn set(args: &[String]) -> i32 {
+    if args.is_empty() {
+        for (name, var) in variables::all() {
+            if let Some(value) = var.value {
+                println!("{}={}", name, format_value(&value));
+            }
+        }
+        return 0;
+    }
+
+    let mut it = args.iter();
+    // operands replace the positional parameters, `set --` alone clearing them
+    let mut operands = None;
+    while let Some(arg) = it.next() {
+        let enabled = arg.starts_with('-');
+        if arg == "--" {
+            operands = Some(it.as_slice());
+            break;
+        }
+        if arg.len() < 2 || !(enabled || arg.starts_with('+')) {
+            operands = Some(&args[args.len() - it.as_slice().len() - 1..]);
+            break;
+        }
+
+        for letter in arg[1..].chars() {
+            let result = match letter {
+                'o' => match it.next() {
+                    Some(name) => options::set(name, enabled),
+                    None => {
+                        print_options(!enabled);
+                        Ok(())
+                    }
+                },
+                _ => options::by_letter(letter)
+                    .ok_or_else(|| format!("{}{}: invalid option", &arg[..1], letter))
+                    .and_then(|name| options::set(name, enabled)),
+            };
+            if let Err(e) = result {
+                eprintln!("mysh: set: {}", e);
+                return 2;
+            }
+        }
+    }
+    if let Some(operands) = operands {
+        variables::set_positional(operands.to_vec());
+    }
+    0
+}
+
+/// `shift [n]`
+pub fn shift(args: &[String]) -> i32 {
+    let n = match args.first().map(|arg| arg.parse::<usize>()) {
+        None => 1,
+        Some(Ok(n)) => n,
+        Some(Err(_)) => {
+            eprintln!("mysh: shift: {}: numeric argument required", args[0]);
+            return 1;
+        }
+    };
+    let positional = variables::positional();
+    if n > positional.len() {
+        return 1;
+    }
+    variables::set_positional(positional[n..].to_vec());
+    0
+}
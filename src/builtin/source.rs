@@ -0,0 +1,83 @@
+//! `source` and `.` builtins, running a file in the current shell
+
+use crate::interpret::{self, Reading};
+use crate::traps::{self, Condition};
+use crate::variables;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+// a name without a slash is looked up in PATH first, then in the current directory
+fn find_file(name: &str) -> PathBuf {
+    if !name.contains('/') {
+        let path = variables::get("PATH").unwrap_or_default();
+        let found = env::split_paths(&path).map(|dir| dir.join(name)).find(|file| file.is_file());
+        if let Some(file) = found {
+            return file;
+        }
+    }
+    PathBuf::from(name)
+}
+
+/// Runs the commands of a file in the current shell
+pub fn source_file(path: &Path) -> io::Result<()> {
+    let file = File::open(path)?;
+    interpret::interpret_script(&mut BufReader::new(file), Reading::Sourced)
+}
+
+/// `source filename [arguments]`, the arguments becoming the positional parameters while it runs
+pub fn source(args: &[String]) -> i32 {
+    let (name, args) = match args.split_first() {
+        Some((name, args)) => (name, args),
+        None => {
+            eprintln!("mysh: source: filename argument required");
+            eprintln!("source: usage: source filename [arguments]");
+            return 2;
+        }
+    };
+
+    let file = match File::open(find_file(name)) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("mysh: {}: {}", name, e);
+            return 1;
+        }
+    };
+    let saved = if args.is_empty() {
+        None
+    } else {
+        let saved = variables::positional();
+        variables::set_positional(args.to_vec());
+        Some(saved)
+    };
+
+    interpret::set_last_status(0);
+    let status = match interpret::interpret_script(&mut BufReader::new(file), Reading::Sourced) {
+        Ok(()) => interpret::last_status(),
+        Err(e) => {
+            eprintln!("mysh: {}: {}", name, e);
+            2
+        }
+    };
+    traps::run(Condition::Return);
+
+    if let Some(positional) = saved {
+        variables::set_positional(positional);
+    }
+    status
+}
+
+#[test]
+fn test_source() {
+    let path = env::temp_dir().join(format!("mysh-test-source-{}", std::process::id()));
+    std::fs::write(&path, "TEST_SOURCE_VAR=\"$1\"\necho \\\n  continued\n").unwrap();
+    let args = vec![path.to_string_lossy().into_owned(), "arg".to_string()];
+    assert_eq!(source(&args), 0);
+    assert_eq!(variables::get("TEST_SOURCE_VAR"), Some("arg".to_string()));
+
+    std::fs::write(&path, "TEST_SOURCE_VAR=1\n\"unterminated\n").unwrap();
+    assert_eq!(source(&args[..1]), 2);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(source(&args[..1]), 1);
+}
@@ -0,0 +1,68 @@
+//! `trap` builtin, setting commands to run on signals and shell events
+
+use super::kill;
+use crate::quote::quote;
+use crate::traps::{self, Condition};
+
+fn parse_condition(spec: &str) -> Option<Condition> {
+    match &spec.to_ascii_uppercase()[..] {
+        "0" | "EXIT" => Some(Condition::Exit),
+        "ERR" => Some(Condition::Err),
+        "DEBUG" => Some(Condition::Debug),
+        "RETURN" => Some(Condition::Return),
+        _ => kill::parse_signal(spec).map(Condition::Signal),
+    }
+}
+
+fn print_traps(specs: &[String]) -> i32 {
+    let mut status = 0;
+    let mut conditions = vec![];
+    for spec in specs {
+        match parse_condition(spec) {
+            Some(condition) => conditions.push(condition),
+            None => {
+                eprintln!("mysh: trap: {}: invalid signal specification", spec);
+                status = 1;
+            }
+        }
+    }
+
+    for (condition, command) in traps::all() {
+        if specs.is_empty() || conditions.contains(&condition) {
+            println!("trap -- {} {}", quote(&command), condition.name());
+        }
+    }
+    status
+}
+
+/// `trap [-lp] [[action] sigspec ...]`
+pub fn trap(args: &[String]) -> i32 {
+    match args.first().map(|arg| &arg[..]) {
+        None => return print_traps(&[]),
+        Some("-p") => return print_traps(&args[1..]),
+        Some("-l") => return kill::kill(&["-l".to_string()]),
+        _ => (),
+    }
+
+    let args = if args[0] == "--" { &args[1..] } else { args };
+    // a lone signal, or `-` as the action, resets the signals
+    let (action, specs) = match args {
+        [_] => (None, args),
+        [action, specs @ ..] if action == "-" => (None, specs),
+        [action, specs @ ..] => (Some(action), specs),
+        [] => return print_traps(&[]),
+    };
+
+    let mut status = 0;
+    for spec in specs {
+        let result = match parse_condition(spec) {
+            Some(condition) => traps::set(condition, action.cloned()),
+            None => Err(format!("{}: invalid signal specification", spec)),
+        };
+        if let Err(e) = result {
+            eprintln!("mysh: trap: {}", e);
+            status = 1;
+        }
+    }
+    status
+}
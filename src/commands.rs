@@ -0,0 +1,307 @@
+//! Commands module resolves command names the way they are run: as an alias, a
+//! builtin or a program found along `PATH`. Programs that were looked up are
+//! remembered in the hash table until `PATH` changes or `hash -r` empties it.
+
+use crate::aliases;
+use crate::builtin;
+use crate::variables;
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// What a command name stands for
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    /// Alias, with its value
+    Alias(String),
+    Builtin,
+    /// Program, with whether its location came from the hash table
+    File(PathBuf, bool),
+}
+
+/// Location of a program along with how many times it was run from there
+struct Hashed {
+    path: PathBuf,
+    hits: u32,
+}
+
+#[derive(Default)]
+struct HashTable {
+    /// `PATH` the programs were found along
+    path_var: String,
+    entries: BTreeMap<String, Hashed>,
+}
+
+lazy_static! {
+    static ref HASH_TABLE: Mutex<HashTable> = Mutex::new(HashTable::default());
+}
+
+/// Searched while `PATH` is unset, as `execvp` does
+const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+fn path_var() -> String {
+    variables::get("PATH").unwrap_or_else(|| DEFAULT_PATH.to_string())
+}
+
+// the hash table, emptied first when `PATH` changed since it was filled
+fn table() -> MutexGuard<'static, HashTable> {
+    let path_var = path_var();
+    let mut table = HASH_TABLE.lock().unwrap();
+    if table.path_var != path_var {
+        table.entries.clear();
+        table.path_var = path_var;
+    }
+    table
+}
+
+/// Whether the path is a regular file with an execute bit set
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Every program with the name along `PATH`, in the order they are searched
+pub fn search_path(name: &str) -> Vec<PathBuf> {
+    search_dirs(&path_var(), name)
+}
+
+// directories of a `PATH` value, an empty entry standing for the current directory
+fn path_dirs(path_var: &str) -> impl Iterator<Item = &Path> {
+    path_var.split(':').map(|dir| Path::new(if dir.is_empty() { "." } else { dir }))
+}
+
+fn search_dirs(path_var: &str, name: &str) -> Vec<PathBuf> {
+    path_dirs(path_var).map(|dir| dir.join(name)).filter(|path| is_executable(path)).collect()
+}
+
+// a name with a slash is a path to the program rather than something to search for
+fn find_uncached(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        Some(PathBuf::from(name)).filter(|path| is_executable(path))
+    } else {
+        search_path(name).into_iter().next()
+    }
+}
+
+/// Location of the program the name runs, counting a hit for it in the hash table.
+/// A hashed program that went away is searched for again.
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        return find_uncached(name);
+    }
+    let mut table = table();
+    if let Some(hashed) = table.entries.get_mut(name).filter(|hashed| is_executable(&hashed.path)) {
+        hashed.hits += 1;
+        return Some(hashed.path.clone());
+    }
+    match find_uncached(name) {
+        Some(path) => {
+            table.entries.insert(name.to_string(), Hashed { path: path.clone(), hits: 1 });
+            Some(path)
+        }
+        None => {
+            table.entries.remove(name);
+            None
+        }
+    }
+}
+
+/// Location of the program along a `PATH` given to a single command,
+/// which the hash table is left out of
+pub fn find_program_along(path_var: &str, name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        find_uncached(name)
+    } else {
+        search_dirs(path_var, name).into_iter().next()
+    }
+}
+
+/// Searches for the program and remembers where it is without counting a hit,
+/// returning whether it was found
+pub fn hash(name: &str) -> bool {
+    if name.contains('/') || builtin::find_builtin(name).is_some() {
+        return true;
+    }
+    let path = match find_uncached(name) {
+        Some(path) => path,
+        None => return false,
+    };
+    table().entries.insert(name.to_string(), Hashed { path, hits: 0 });
+    true
+}
+
+/// Location of the program remembered for the name
+pub fn hashed_path(name: &str) -> Option<PathBuf> {
+    table().entries.get(name).map(|hashed| hashed.path.clone())
+}
+
+/// Every remembered program as name, location and hits, sorted by name
+pub fn hashed() -> Vec<(String, PathBuf, u32)> {
+    table()
+        .entries
+        .iter()
+        .map(|(name, hashed)| (name.clone(), hashed.path.clone(), hashed.hits))
+        .collect()
+}
+
+/// Forgets where the program is, returning whether it was remembered
+pub fn forget(name: &str) -> bool {
+    table().entries.remove(name).is_some()
+}
+
+pub fn forget_all() {
+    table().entries.clear();
+}
+
+/// What the name runs as, without touching the hash table
+pub fn resolve(name: &str) -> Option<Resolution> {
+    if let Some(value) = aliases::get(name) {
+        return Some(Resolution::Alias(value));
+    }
+    if builtin::find_builtin(name).is_some() {
+        return Some(Resolution::Builtin);
+    }
+    match hashed_path(name).filter(|path| is_executable(path)) {
+        Some(path) => Some(Resolution::File(path, true)),
+        None => find_uncached(name).map(|path| Resolution::File(path, false)),
+    }
+}
+
+/// Everything the name could run as, in the order they are tried,
+/// with every program of that name along `PATH`
+pub fn resolve_all(name: &str) -> Vec<Resolution> {
+    let mut resolutions = vec![];
+    if let Some(value) = aliases::get(name) {
+        resolutions.push(Resolution::Alias(value));
+    }
+    if builtin::find_builtin(name).is_some() {
+        resolutions.push(Resolution::Builtin);
+    }
+    let paths = if name.contains('/') {
+        find_uncached(name).into_iter().collect()
+    } else {
+        search_path(name)
+    };
+    resolutions.extend(paths.into_iter().map(|path| Resolution::File(path, false)));
+    resolutions
+}
+
+/// Why the name didn't run as a program, with the exit status that goes with it:
+/// a file by that name that can't be executed gives 126, a missing one 127.
+/// `path_var` is the `PATH` given to the command alone, if it was.
+pub fn failure(name: &str, path_var: Option<&str>) -> (&'static str, i32) {
+    let file = if name.contains('/') {
+        Some(PathBuf::from(name)).filter(|path| path.exists())
+    } else {
+        let path_var = path_var.map_or_else(self::path_var, String::from);
+        let file = path_dirs(&path_var).map(|dir| dir.join(name)).find(|path| path.is_file());
+        file
+    };
+    match file {
+        Some(ref path) if path.is_dir() => ("Is a directory", 126),
+        Some(_) => ("Permission denied", 126),
+        None if name.contains('/') => ("No such file or directory", 127),
+        None => ("command not found", 127),
+    }
+}
+
+// number of characters to insert, delete, replace or swap with their neighbour
+// to turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// Builtins and programs along `PATH`, or along the `PATH` given to the command
+/// alone, with names close to the one that wasn't found, the closest first
+pub fn suggestions(name: &str, path_var: Option<&str>) -> Vec<String> {
+    let limit = (name.chars().count() / 4).max(1);
+    let mut candidates: Vec<String> = builtin::builtin_names().map(String::from).collect();
+    let path_var = path_var.map_or_else(self::path_var, String::from);
+    for dir in path_dirs(&path_var) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if is_executable(&entry.path()) {
+                candidates.extend(entry.file_name().into_string());
+            }
+        }
+    }
+
+    let mut close: Vec<_> = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, &candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .collect();
+    close.sort();
+    close.dedup();
+    close.into_iter().take(3).map(|(_, candidate)| candidate).collect()
+}
+
+#[test]
+fn test_search_dirs() {
+    let dir = std::env::temp_dir().join(format!("mysh-test-commands-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("test_prog");
+    std::fs::write(&program, "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::write(dir.join("test_data"), "").unwrap();
+
+    let path_var = format!("/nonexistent:{}:{}", dir.display(), dir.display());
+    assert_eq!(search_dirs(&path_var, "test_prog"), vec![program.clone(), program.clone()]);
+    assert!(search_dirs(&path_var, "test_data").is_empty());
+    assert!(search_dirs(&path_var, "missing").is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_resolve() {
+    assert_eq!(resolve("cd"), Some(Resolution::Builtin));
+    assert_eq!(resolve("mysh-test-missing"), None);
+
+    let sh = match find_program("sh") {
+        Some(sh) => sh,
+        None => return,
+    };
+    assert_eq!(resolve("sh"), Some(Resolution::File(sh.clone(), true)));
+    assert_eq!(find_program("sh"), Some(sh.clone()));
+    assert!(hashed().contains(&("sh".to_string(), sh.clone(), 2)));
+    assert!(forget("sh"));
+    assert_eq!(resolve("sh"), Some(Resolution::File(sh, false)));
+}
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("gti", "git"), 1);
+    assert_eq!(edit_distance("sl", "ls"), 1);
+    assert_eq!(edit_distance("grpe", "grep"), 1);
+    assert_eq!(edit_distance("mkae", "make"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("", "cd"), 2);
+    assert!(suggestions("pusd", None).contains(&"pushd".to_string()));
+}
@@ -0,0 +1,70 @@
+//! Dirstack module holds the directory stack of `pushd`, `popd` and `dirs`.
+//!
+//! The top of the stack is always the current directory, only the
+//! directories below it are stored.
+
+use crate::variables;
+use lazy_static::lazy_static;
+use std::env;
+use std::sync::Mutex;
+
+lazy_static! {
+    /// Directories below the top of the stack
+    static ref SAVED: Mutex<Vec<String>> = Mutex::new(vec![]);
+}
+
+fn current_dir() -> String {
+    variables::get("PWD").unwrap_or_else(|| env::current_dir().unwrap_or_default().to_string_lossy().into_owned())
+}
+
+/// Every entry of the stack, the current directory first
+pub fn entries() -> Vec<String> {
+    let mut entries = vec![current_dir()];
+    entries.extend(SAVED.lock().unwrap().iter().cloned());
+    entries
+}
+
+/// Replaces the directories below the top of the stack
+pub fn set_saved(saved: Vec<String>) {
+    *SAVED.lock().unwrap() = saved;
+}
+
+/// Whether the argument is a `+N` or `-N` stack index rather than a directory
+pub fn is_index(spec: &str) -> bool {
+    (spec.starts_with('+') || spec.starts_with('-')) && spec.len() > 1 && spec[1..].chars().all(|ch| ch.is_ascii_digit())
+}
+
+/// Position of the `+N` entry counting from the left or of the `-N` one counting
+/// from the right, both starting at zero, in a stack of `len` entries
+pub fn index(spec: &str, len: usize) -> Option<usize> {
+    if !is_index(spec) {
+        return None;
+    }
+    let n: usize = spec[1..].parse().ok().filter(|&n| n < len)?;
+    Some(if spec.starts_with('-') { len - 1 - n } else { n })
+}
+
+/// Entry named by `N`, `+N` or `-N`, as `~N` refers to it
+pub fn entry(spec: &str) -> Option<String> {
+    let spec = if spec.starts_with(|ch: char| ch.is_ascii_digit()) {
+        format!("+{}", spec)
+    } else {
+        spec.to_string()
+    };
+    let mut entries = entries();
+    index(&spec, entries.len()).map(|n| entries.swap_remove(n))
+}
+
+#[test]
+fn test_dirstack() {
+    set_saved(vec!["/a".to_string(), "/b".to_string()]);
+    assert_eq!(entry("1"), Some("/a".to_string()));
+    assert_eq!(entry("+2"), Some("/b".to_string()));
+    assert_eq!(entry("-0"), Some("/b".to_string()));
+    assert_eq!(entry("-2"), Some(current_dir()));
+    assert_eq!(entry("3"), None);
+    assert_eq!(entry("x"), None);
+    assert!(!is_index("+"));
+    assert!(!is_index("-L"));
+    set_saved(vec![]);
+}
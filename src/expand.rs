@@ -0,0 +1,495 @@
+//! Expand module turns the raw words kept by the lexer into command arguments.
+//! Performs tilde and parameter expansion, field splitting on IFS and quote removal.
+
+use crate::dirstack;
+use crate::interpret;
+use crate::jobs;
+use crate::lexer::Tokenizer;
+use crate::options;
+use crate::parser::Token;
+use crate::variables::{self, Assignment};
+use nix::unistd;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// Result of expanding a parameter
+enum Parameter {
+    Value(String),
+    /// `${arr[@]}` and `$@` or, when joined, `${arr[*]}` and `$*`
+    Elements(Vec<String>, bool),
+}
+
+/// Fields built up while expanding a word
+struct Fields {
+    fields: Vec<String>,
+    /// field being built, `None` until anything is added to it
+    current: Option<String>,
+    /// whether unquoted expansions are split on IFS
+    split: bool,
+    ifs: String,
+    /// the last field was ended by IFS whitespace, which absorbs a following delimiter
+    ended_by_whitespace: bool,
+}
+
+impl Fields {
+    fn new(ifs: &str, split: bool) -> Self {
+        Fields {
+            fields: vec![],
+            current: None,
+            split,
+            ifs: ifs.to_string(),
+            ended_by_whitespace: false,
+        }
+    }
+
+    // separator joining `"${arr[*]}"`, the first character of IFS
+    fn separator(&self) -> String {
+        self.ifs.chars().take(1).collect()
+    }
+
+    /// Adds literal or quoted text, which is never split
+    fn push_str(&mut self, s: &str) {
+        self.current.get_or_insert_with(String::new).push_str(s);
+        self.ended_by_whitespace = false;
+    }
+
+    fn push(&mut self, ch: char) {
+        self.push_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    fn end_field(&mut self) {
+        if let Some(field) = self.current.take() {
+            self.fields.push(field);
+        }
+    }
+
+    /// Adds the result of an unquoted expansion, splitting it on IFS characters.
+    /// IFS whitespace only separates fields while any other IFS character
+    /// delimits one, possibly empty, field.
+    fn push_split(&mut self, value: &str) {
+        if !self.split {
+            return self.push_str(value);
+        }
+
+        for ch in value.chars() {
+            if !self.ifs.contains(ch) {
+                self.push(ch);
+            } else if " \t\n".contains(ch) {
+                if self.current.is_some() {
+                    self.end_field();
+                    self.ended_by_whitespace = true;
+                }
+            } else {
+                if self.current.is_some() {
+                    self.end_field();
+                } else if !self.ended_by_whitespace {
+                    self.fields.push(String::new());
+                }
+                self.ended_by_whitespace = false;
+            }
+        }
+    }
+
+    // the first element joins the current field, the others start new fields
+    fn push_elements(&mut self, elements: Vec<String>, quoted: bool) {
+        // unquoted elements are joined and split again, like `$*`
+        if !quoted && self.split && !self.ifs.is_empty() {
+            let joined = elements.join(&self.separator());
+            return self.push_split(&joined);
+        }
+
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 {
+                if self.split {
+                    self.end_field();
+                } else {
+                    self.push_str(" ");
+                }
+            }
+
+            if quoted {
+                self.push_str(element);
+            } else {
+                self.push_split(element);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        self.end_field();
+        self.fields
+    }
+}
+
+fn ifs() -> String {
+    variables::get("IFS").unwrap_or_else(|| " \t\n".to_string())
+}
+
+/// Value of the special parameters `$?`, `$!`, `$$`, `$-`, `$#`, `$@`, `$*`, `$0`
+/// and of the positional parameters `$1`, `$2`, ...
+fn special_parameter(name: &str) -> Result<Option<Parameter>, String> {
+    let value = match name {
+        "0" => variables::arg0(),
+        "?" => interpret::last_status().to_string(),
+        "!" => jobs::last_background_pid().map_or(String::new(), |pid| pid.to_string()),
+        "$" => unistd::getpid().to_string(),
+        "-" => options::flags(),
+        "#" => variables::positional().len().to_string(),
+        "@" => return Ok(Some(Parameter::Elements(variables::positional(), false))),
+        "*" => return Ok(Some(Parameter::Elements(variables::positional(), true))),
+        _ if !name.is_empty() && name.chars().all(|ch| ch.is_ascii_digit()) => {
+            let n: usize = name.parse().map_err(|_| format!("${{{}}}: bad substitution", name))?;
+            variable_value(name, n.checked_sub(1).and_then(|i| variables::positional().get(i).cloned()))?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(Parameter::Value(value)))
+}
+
+/// How an unset variable expanded under `set -u` is reported, after its name
+const UNBOUND: &str = ": unbound variable";
+
+/// Whether the expansion error is an unset variable under `set -u`,
+/// which ends a shell that isn't interactive
+pub fn is_unbound_error(message: &str) -> bool {
+    message.ends_with(UNBOUND)
+}
+
+// value of a variable, unset ones being an error under `set -u`
+fn variable_value(name: &str, value: Option<String>) -> Result<String, String> {
+    match value {
+        Some(value) => Ok(value),
+        None if options::is_set("nounset") => Err(format!("{}{}", name, UNBOUND)),
+        None => Ok(String::new()),
+    }
+}
+
+// contents of `${...}`
+fn expand_braced(expr: &str) -> Result<Parameter, String> {
+    if let Some(parameter) = special_parameter(expr)? {
+        return Ok(parameter);
+    }
+    let bad_substitution = || format!("${{{}}}: bad substitution", expr);
+
+    let (prefix, rest) = match expr.chars().next() {
+        Some(ch @ '#') | Some(ch @ '!') if expr.len() > 1 => (Some(ch), &expr[1..]),
+        _ => (None, expr),
+    };
+    if prefix == Some('#') {
+        match special_parameter(rest)? {
+            Some(Parameter::Value(value)) => return Ok(Parameter::Value(value.chars().count().to_string())),
+            Some(Parameter::Elements(elements, _)) => return Ok(Parameter::Value(elements.len().to_string())),
+            None => (),
+        }
+    }
+    let (name, subscript) = match rest.find('[') {
+        Some(n) if rest.ends_with(']') => (&rest[..n], Some(&rest[n + 1..rest.len() - 1])),
+        _ => (rest, None),
+    };
+    if !variables::is_valid_name(name) {
+        return Err(bad_substitution());
+    }
+
+    let value = || variables::lookup(name).and_then(|var| var.value);
+    let single = match subscript {
+        Some("@") | Some("*") => {
+            let (elements, joined) = match prefix {
+                Some('#') => {
+                    let count = value().map_or(0, |value| value.elements().len());
+                    return Ok(Parameter::Value(count.to_string()));
+                }
+                Some('!') => (value().map_or(vec![], |value| value.keys()), subscript == Some("*")),
+                _ => (value().map_or(vec![], |value| value.elements()), subscript == Some("*")),
+            };
+            return Ok(Parameter::Elements(elements, joined));
+        }
+        Some(subscript) => variables::get_element(name, &expand_string(subscript)?)?,
+        None => variables::get(name),
+    };
+
+    match prefix {
+        Some('#') => Ok(Parameter::Value(single.map_or(0, |s| s.chars().count()).to_string())),
+        Some(_) => Err(bad_substitution()),
+        None => Ok(Parameter::Value(variable_value(rest, single)?)),
+    }
+}
+
+// `$name` or `${...}`, the iterator positioned just after the '$'
+fn expand_parameter(word: &str, it: &mut Peekable<CharIndices<'_>>) -> Result<Parameter, String> {
+    match it.peek() {
+        Some(&(start, '{')) => {
+            it.next();
+            let end = it
+                .find(|&(_, ch)| ch == '}')
+                .map(|(i, _)| i)
+                .ok_or("bad substitution: no closing `}'")?;
+            expand_braced(&word[start + 1..end])
+        }
+        Some(&(start, ch)) if ch.is_ascii_alphabetic() || ch == '_' => {
+            let mut end = word.len();
+            while let Some(&(i, ch)) = it.peek() {
+                if !(ch.is_ascii_alphanumeric() || ch == '_') {
+                    end = i;
+                    break;
+                }
+                it.next();
+            }
+            let name = &word[start..end];
+            Ok(Parameter::Value(variable_value(name, variables::get(name))?))
+        }
+        // a single digit, `${10}` being needed for the tenth positional parameter
+        Some(&(start, ch)) if "?!$-#@*".contains(ch) || ch.is_ascii_digit() => {
+            it.next();
+            Ok(special_parameter(&word[start..start + 1])?.unwrap())
+        }
+        // a lone '$' stays literal
+        _ => Ok(Parameter::Value("$".to_string())),
+    }
+}
+
+// an unquoted `~`, `~+`, `~-` or directory stack `~N`, `~+N`, `~-N` starting the word,
+// along with where the rest of the word starts
+fn expand_tilde(word: &str) -> Option<(String, usize)> {
+    if !word.starts_with('~') {
+        return None;
+    }
+    let end = word.find('/').unwrap_or(word.len());
+    let value = match &word[1..end] {
+        "" => variables::get("HOME"),
+        "+" => variables::get("PWD"),
+        "-" => variables::get("OLDPWD"),
+        spec => dirstack::entry(spec),
+    }?;
+    Some((value, end))
+}
+
+fn expand(word: &str, ifs: &str, split: bool) -> Result<Vec<String>, String> {
+    let mut fields = Fields::new(ifs, split);
+    let mut in_double_quotes = false;
+    // `"${arr[@]}"` of an empty array expands to no field at all
+    let mut elements_quoted = false;
+    let mut it = word.char_indices().peekable();
+
+    // the directory is not split, as if quoted
+    if let Some((value, end)) = expand_tilde(word) {
+        fields.push_str(&value);
+        while it.peek().is_some_and(|&(i, _)| i < end) {
+            it.next();
+        }
+    }
+
+    while let Some((_, ch)) = it.next() {
+        match ch {
+            '\'' if !in_double_quotes => {
+                let literal: String = it.by_ref().map(|(_, ch)| ch).take_while(|&ch| ch != '\'').collect();
+                fields.push_str(&literal);
+            }
+            '"' => {
+                if in_double_quotes && !elements_quoted {
+                    fields.push_str("");
+                }
+                elements_quoted = false;
+                in_double_quotes = !in_double_quotes;
+            }
+            '\\' => match it.next() {
+                // line continuation
+                Some((_, '\n')) => (),
+                // inside double quotes the backslash only escapes characters special there
+                Some((_, ch)) if in_double_quotes && !"$`\"\\\n".contains(ch) => {
+                    fields.push('\\');
+                    fields.push(ch);
+                }
+                Some((_, ch)) => fields.push(ch),
+                None => fields.push('\\'),
+            },
+            '$' => match expand_parameter(word, &mut it)? {
+                Parameter::Value(value) if in_double_quotes => fields.push_str(&value),
+                Parameter::Value(value) => fields.push_split(&value),
+                Parameter::Elements(elements, true) if in_double_quotes => {
+                    fields.push_str(&elements.join(&fields.separator()))
+                }
+                Parameter::Elements(elements, _) => {
+                    elements_quoted |= in_double_quotes;
+                    fields.push_elements(elements, in_double_quotes);
+                }
+            },
+            _ => fields.push(ch),
+        }
+    }
+
+    Ok(fields.finish())
+}
+
+/// Expands a word into fields. Unquoted expansions are split on IFS and
+/// `"${arr[@]}"` produces one field per element.
+pub fn expand_word(word: &str) -> Result<Vec<String>, String> {
+    expand(word, &ifs(), true)
+}
+
+/// Expands a word into a single string without field splitting, as for assignment values
+pub fn expand_string(word: &str) -> Result<String, String> {
+    Ok(expand(word, &ifs(), false)?.join(" "))
+}
+
+/// Words inside the parentheses of `name=(...)`, with optional `[subscript]=` prefixes
+pub fn expand_array_literal(literal: &str) -> Result<Vec<(Option<String>, String)>, String> {
+    let tokens = literal.tokenize().map_err(|e| e.to_string())?;
+    let mut elements = vec![];
+    for tok in tokens.get_stream() {
+        let word = match tok {
+            Token::VarString(word) => word,
+            tok => return Err(format!("syntax error near unexpected token {:?}", tok)),
+        };
+
+        match word.find("]=") {
+            Some(n) if word.starts_with('[') => {
+                let subscript = expand_string(&word[1..n])?;
+                elements.push((Some(subscript), expand_string(&word[n + 2..])?));
+            }
+            _ => elements.extend(expand_word(word)?.into_iter().map(|field| (None, field))),
+        }
+    }
+    Ok(elements)
+}
+
+/// `name=(...)` words, left unexpanded for declaration builtins like `declare`
+pub fn is_array_literal_assignment(word: &str) -> bool {
+    match word.find('=') {
+        Some(n) => variables::is_valid_name(&word[..n]) && word[n + 1..].starts_with('(') && word.ends_with(')'),
+        None => false,
+    }
+}
+
+/// Expands a `name=value`, `name[subscript]=value` or `name=(...)` word
+pub fn expand_assignment(word: &str) -> Result<Assignment, String> {
+    let n = word.find('=').ok_or_else(|| format!("{}: not an assignment", word))?;
+    let (lhs, value) = (&word[..n], &word[n + 1..]);
+
+    if let Some(open) = lhs.find('[') {
+        let subscript = expand_string(&lhs[open + 1..lhs.len() - 1])?;
+        Ok(Assignment::Element(lhs[..open].to_string(), subscript, expand_string(value)?))
+    } else if is_array_literal_assignment(word) {
+        let elements = expand_array_literal(&value[1..value.len() - 1])?;
+        Ok(Assignment::Array(lhs.to_string(), elements))
+    } else {
+        Ok(Assignment::Scalar(lhs.to_string(), expand_string(value)?))
+    }
+}
+
+#[test]
+fn test_expand_word() {
+    variables::set("TEST_EXPAND_VAR", "a b").unwrap();
+
+    let expand = |word| expand_string(word).unwrap();
+    assert_eq!(expand("'sle''ep'"), "sleep");
+    assert_eq!(expand("x$TEST_EXPAND_VAR-y"), "xa b-y");
+    assert_eq!(expand("${TEST_EXPAND_VAR}c"), "a bc");
+    assert_eq!(expand("'$TEST_EXPAND_VAR'"), "$TEST_EXPAND_VAR");
+    assert_eq!(expand("\"$TEST_EXPAND_VAR\\$\\n\""), "a b$\\n");
+    assert_eq!(expand("\\$TEST_EXPAND_VAR$"), "$TEST_EXPAND_VAR$");
+    assert_eq!(expand("$TEST_EXPAND_UNSET"), "");
+    assert_eq!(expand("${#TEST_EXPAND_VAR}"), "3");
+    assert_eq!(expand("\"a\\\nb\""), "ab");
+    assert!(expand_word("${1x}").is_err());
+}
+
+#[test]
+fn test_expand_arrays() {
+    let assignment = expand_assignment("TEST_EXPAND_ARR=(a 'b c' [5]=\"d\")").unwrap();
+    variables::assign(&assignment).unwrap();
+
+    assert_eq!(expand_word("\"${TEST_EXPAND_ARR[@]}\""), Ok(vec!["a".to_string(), "b c".to_string(), "d".to_string()]));
+    assert_eq!(expand_word("\"x${TEST_EXPAND_ARR[*]}y\""), Ok(vec!["xa b c dy".to_string()]));
+    assert_eq!(expand_string("${TEST_EXPAND_ARR[@]}"), Ok("a b c d".to_string()));
+    assert_eq!(expand_word("${!TEST_EXPAND_ARR[@]}"), Ok(vec!["0".to_string(), "1".to_string(), "5".to_string()]));
+    assert_eq!(expand_string("${#TEST_EXPAND_ARR[@]} ${TEST_EXPAND_ARR[1]} $TEST_EXPAND_ARR"), Ok("3 b c a".to_string()));
+    assert_eq!(expand_word("\"${TEST_EXPAND_EMPTY[@]}\""), Ok(vec![]));
+    assert_eq!(expand_word("\"\""), Ok(vec![String::new()]));
+
+    assert_eq!(
+        expand_assignment("TEST_EXPAND_ARR[1+1]=${TEST_EXPAND_ARR[5]}"),
+        Ok(Assignment::Element("TEST_EXPAND_ARR".to_string(), "1+1".to_string(), "d".to_string()))
+    );
+}
+
+#[test]
+fn test_field_splitting() {
+    let elements = "(' a  b ' '' c:d '')";
+    variables::assign(&expand_assignment(&format!("TEST_SPLIT_ARR={}", elements)).unwrap()).unwrap();
+    variables::assign(&Assignment::Array("TEST_SPLIT_EMPTY".to_string(), vec![])).unwrap();
+    variables::set("TEST_SPLIT_VAR", " :a: :b::c d ").unwrap();
+    variables::set("TEST_SPLIT_BLANK", "  ").unwrap();
+
+    let split = |word, ifs| expand(word, ifs, true).unwrap();
+    let fields = |fields: &[&str]| fields.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    let default_ifs = " \t\n";
+
+    // whitespace is trimmed and collapsed, quoted results stay intact
+    assert_eq!(split("$TEST_SPLIT_VAR", default_ifs), fields(&[":a:", ":b::c", "d"]));
+    assert_eq!(split("\"$TEST_SPLIT_VAR\"", default_ifs), fields(&[" :a: :b::c d "]));
+    assert_eq!(split("x${TEST_SPLIT_VAR}y", default_ifs), fields(&["x", ":a:", ":b::c", "d", "y"]));
+
+    // non-whitespace delimiters produce empty fields, absorbing adjacent whitespace
+    assert_eq!(split("$TEST_SPLIT_VAR", " :"), fields(&["", "a", "", "b", "", "c", "d"]));
+    assert_eq!(split("$TEST_SPLIT_VAR", ":"), fields(&[" ", "a", " ", "b", "", "c d "]));
+    assert_eq!(split("$TEST_SPLIT_VAR", ""), fields(&[" :a: :b::c d "]));
+
+    // unquoted empty results vanish, quoted ones remain as empty arguments
+    assert_eq!(split("$TEST_SPLIT_BLANK", default_ifs), fields(&[]));
+    assert_eq!(split("$TEST_SPLIT_UNSET", default_ifs), fields(&[]));
+    assert_eq!(split("\"$TEST_SPLIT_UNSET\"", default_ifs), fields(&[""]));
+    assert_eq!(split("''$TEST_SPLIT_UNSET", default_ifs), fields(&[""]));
+
+    // "${arr[@]}" keeps every element, including empty ones, as its own field
+    assert_eq!(split("\"${TEST_SPLIT_ARR[@]}\"", default_ifs), fields(&[" a  b ", "", "c:d", ""]));
+    assert_eq!(split("\"<${TEST_SPLIT_ARR[@]}>\"", default_ifs), fields(&["< a  b ", "", "c:d", ">"]));
+    assert_eq!(split("\"${TEST_SPLIT_EMPTY[@]}\"", default_ifs), fields(&[]));
+    assert_eq!(split("\"${TEST_SPLIT_EMPTY[@]}\"''", default_ifs), fields(&[""]));
+
+    // unquoted ${arr[@]} and ${arr[*]} join the elements and split the result
+    assert_eq!(split("${TEST_SPLIT_ARR[@]}", default_ifs), fields(&["a", "b", "c:d"]));
+    assert_eq!(split("${TEST_SPLIT_ARR[*]}", ":"), fields(&[" a  b ", "", "c", "d"]));
+    assert_eq!(split("${TEST_SPLIT_ARR[@]}", ""), fields(&[" a  b ", "c:d"]));
+
+    // "${arr[*]}" joins with the first character of IFS
+    assert_eq!(split("\"${TEST_SPLIT_ARR[*]}\"", ":"), fields(&[" a  b ::c:d:"]));
+    assert_eq!(split("\"${TEST_SPLIT_ARR[*]}\"", ""), fields(&[" a  b c:d"]));
+
+    // assignments are never split
+    assert_eq!(expand("$TEST_SPLIT_VAR", default_ifs, false), Ok(fields(&[" :a: :b::c d "])));
+}
+
+#[test]
+fn test_special_parameters() {
+    let pid = unistd::getpid().to_string();
+    assert_eq!(expand_word("$$"), Ok(vec![pid.clone()]));
+    assert_eq!(expand_word("\"${$}\"x"), Ok(vec![format!("{}x", pid)]));
+    assert!(expand_string("$?").unwrap().parse::<i32>().is_ok());
+    assert_eq!(expand_word("a$"), Ok(vec!["a$".to_string()]));
+}
+
+#[test]
+fn test_positional_parameters() {
+    let args = ["a b", "", "c", "4", "5", "6", "7", "8", "9", "ten"];
+    variables::set_positional(args.iter().map(|arg| arg.to_string()).collect());
+    assert_eq!(expand_word("$#"), Ok(vec!["10".to_string()]));
+    assert_eq!(expand_word("$1"), Ok(vec!["a".to_string(), "b".to_string()]));
+    assert_eq!(expand_word("\"$10\"-${10}"), Ok(vec!["a b0-ten".to_string()]));
+    assert_eq!(expand_word("${#1}"), Ok(vec!["3".to_string()]));
+    assert_eq!(expand_word("\"$@\"").unwrap().len(), 10);
+    assert_eq!(expand_word("\"$@\"").unwrap()[1], "");
+    assert_eq!(expand_word("$@").unwrap().len(), 10);
+    assert_eq!(expand_word("\"$*\"").unwrap().len(), 1);
+    variables::set_positional(vec![]);
+    assert_eq!(expand_word("\"$@\""), Ok(vec![]));
+    assert_eq!(expand_word("\"$*\""), Ok(vec!["".to_string()]));
+}
+
+#[test]
+fn test_tilde() {
+    variables::set("OLDPWD", "/old dir").unwrap();
+    assert_eq!(expand_word("~-/x"), Ok(vec!["/old dir/x".to_string()]));
+    assert_eq!(expand_word("\"~-\""), Ok(vec!["~-".to_string()]));
+    assert_eq!(expand_word("a~-"), Ok(vec!["a~-".to_string()]));
+    assert_eq!(expand_word("~nosuchuser"), Ok(vec!["~nosuchuser".to_string()]));
+}
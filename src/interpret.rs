@@ -1,126 +1,666 @@
 //! Interpret module interprets the parsed AST 
 
 use crate::builtin::*;
-use ::nix;
-use ::nix::unistd::Pid;
+use crate::commands;
+use crate::expand::*;
+use crate::jobs::{self, Job};
+use crate::lexer::{Tokenizer, Tokens};
+use crate::options;
+use crate::quote::quote;
+use crate::traps::{self, disable_shell_signal_handlers, Condition};
+use crate::variables::{self, Assignment};
+use ::nix::errno::Errno;
+use ::nix::fcntl::OFlag;
+use ::nix::unistd::{self, ForkResult, Pid};
 use crate::parser::*;
+use std::fmt;
 use std::fs::File;
 use std::io::Result;
-use std::io::{Error, ErrorKind};
+use std::io::{self, Error, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 use std::os::unix::process::CommandExt;
-use std::process::{Command, Stdio};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 
-pub fn interpret_simplecmd_expr(expr: &SimpleCmdExpr) -> Command {
-    match expr {
-        SimpleCmdExpr::Exe(exepath) => {
-            let mut cmd = Command::new(exepath);
-            unsafe {
-                cmd.pre_exec(|| {
-                    disable_shell_signal_handlers();
-                    Ok(())
-                });
-            }
-            cmd
+/// Exit status of the last foreground job
+static LAST_STATUS: AtomicI32 = AtomicI32::new(0);
+
+pub fn last_status() -> i32 {
+    LAST_STATUS.load(Ordering::SeqCst)
+}
+
+pub fn set_last_status(status: i32) {
+    LAST_STATUS.store(status, Ordering::SeqCst);
+}
+
+/// Records the exit status of each command of the last foreground pipeline in `PIPESTATUS`
+pub fn set_pipestatus(statuses: &[i32]) {
+    let elements = statuses.iter().map(|status| (None, status.to_string())).collect();
+    variables::assign(&Assignment::Array("PIPESTATUS".to_string(), elements)).ok();
+}
+
+/// What a simple command turns into once its words are expanded
+pub enum SimpleCmd {
+    /// External program ready to be spawned
+    Process(Command),
+    /// Builtin with its arguments and the assignments scoped to it
+    Builtin(BuiltinFn, Vec<String>, Vec<Assignment>),
+    /// Assignments without a command, applied to the shell itself
+    Assign(Vec<Assignment>),
+    /// Name that no builtin or program goes by, with its arguments
+    /// and the `PATH` searched for it when assigned for the command alone
+    NotFound(String, Vec<String>, Option<String>),
+}
+
+/// File opened for a redirection, with the descriptor it replaces
+type Redirect = (RawFd, File);
+
+/// Builtins whose `name=value` arguments are assignments rather than plain words
+const DECLARATION_BUILTINS: &[&str] = &["declare", "export", "readonly"];
+
+fn to_io_error<E>(e: E) -> Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    Error::other(e)
+}
+
+/// Error expanding a word, which abandons the rest of the command line
+#[derive(Debug)]
+struct ExpansionError(String);
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ExpansionError {}
+
+fn to_expansion_error(message: String) -> Error {
+    Error::other(ExpansionError(message))
+}
+
+// message of an expansion error, `None` for errors of any other kind
+fn expansion_error(e: &Error) -> Option<&str> {
+    let inner = e.get_ref()?.downcast_ref::<ExpansionError>()?;
+    Some(&inner.0)
+}
+
+fn expand_fields(word: &str) -> Result<Vec<String>> {
+    expand_word(word).map_err(to_expansion_error)
+}
+
+fn trace_assignment(assignment: &Assignment) -> String {
+    match assignment {
+        Assignment::Scalar(name, value) => format!("{}={}", name, quote(value)),
+        Assignment::Element(name, subscript, value) => format!("{}[{}]={}", name, subscript, quote(value)),
+        Assignment::Array(name, elements) => {
+            let elements: Vec<_> = elements
+                .iter()
+                .map(|(key, value)| match key {
+                    Some(key) => format!("[{}]={}", key, quote(value)),
+                    None => quote(value),
+                })
+                .collect();
+            format!("{}=({})", name, elements.join(" "))
         }
-        SimpleCmdExpr::ExeWithArg(exepath, args) => {
-            let mut cmd = Command::new(exepath);
-            unsafe {
-                cmd.pre_exec(|| {
-                    disable_shell_signal_handlers();
-                    Ok(())
-                });
+    }
+}
+
+/// Expands the words of a simple command, printing them prefixed with `PS4` under `set -x`
+pub fn interpret_simplecmd_expr(expr: &SimpleCmdExpr) -> Result<SimpleCmd> {
+    let mut trace = vec![];
+    let cmd = expand_simplecmd_expr(expr, &mut trace, None)?;
+    if options::is_set("xtrace") && !trace.is_empty() {
+        let ps4 = variables::get("PS4").unwrap_or_else(|| "+ ".to_string());
+        eprintln!("{}{}", expand_string(&ps4).unwrap_or(ps4), trace.join(" "));
+    }
+    Ok(cmd)
+}
+
+// the expanded words are collected in `trace`, programs are searched along
+// `search_path` when `PATH` is assigned for the command alone
+fn expand_simplecmd_expr(
+    expr: &SimpleCmdExpr,
+    trace: &mut Vec<String>,
+    search_path: Option<&str>,
+) -> Result<SimpleCmd> {
+    let (exepath, args) = match expr {
+        SimpleCmdExpr::Exe(exepath) => (exepath, &[][..]),
+        SimpleCmdExpr::ExeWithArg(exepath, args) => (exepath, &args[..]),
+        SimpleCmdExpr::Assign(assignments, cmd_expr) => {
+            let assignments = assignments
+                .iter()
+                .map(|word| expand_assignment(word).map_err(to_expansion_error))
+                .collect::<Result<Vec<_>>>()?;
+            trace.extend(assignments.iter().map(trace_assignment));
+
+            let path_var = assignments.iter().rev().find_map(|assignment| match assignment {
+                Assignment::Scalar(name, value) if name == "PATH" => Some(value.clone()),
+                _ => None,
+            });
+            let search_path = path_var.as_deref().or(search_path);
+
+            return match cmd_expr {
+                None => Ok(SimpleCmd::Assign(assignments)),
+                Some(box cmd_expr) => match expand_simplecmd_expr(cmd_expr, trace, search_path)? {
+                    SimpleCmd::Process(mut cmd) => {
+                        // arrays can't be passed through the environment
+                        for assignment in assignments {
+                            if let Assignment::Scalar(name, value) = assignment {
+                                cmd.env(name, value);
+                            }
+                        }
+                        Ok(SimpleCmd::Process(cmd))
+                    }
+                    SimpleCmd::Builtin(builtin, args, _) => {
+                        Ok(SimpleCmd::Builtin(builtin, args, assignments))
+                    }
+                    SimpleCmd::Assign(_) => Ok(SimpleCmd::Assign(assignments)),
+                    not_found @ SimpleCmd::NotFound(..) => Ok(not_found),
+                },
+            };
+        }
+    };
+
+    let mut fields = expand_fields(exepath)?.into_iter();
+    let mut exepath = match fields.next() {
+        Some(exepath) => exepath,
+        // the command word expanded to nothing
+        None => return Ok(SimpleCmd::Assign(vec![])),
+    };
+
+    let is_declaration = DECLARATION_BUILTINS.contains(&&exepath[..]);
+    let mut expanded_args: Vec<String> = fields.collect();
+    for arg in args {
+        if is_declaration && is_array_literal_assignment(arg) {
+            expanded_args.push(arg.clone());
+        } else if is_declaration && Token::VarString(arg.into()).is_assignment() {
+            // assignment arguments are not subject to field splitting
+            expanded_args.push(expand_string(arg).map_err(to_expansion_error)?);
+        } else {
+            expanded_args.extend(expand_fields(arg)?);
+        }
+    }
+    trace.push(quote(&exepath));
+    trace.extend(expanded_args.iter().map(|arg| quote(arg)));
+
+    // `command name` runs the name itself, as a builtin or program
+    while exepath == "command" {
+        match expanded_args.first().map(String::as_str) {
+            Some("--") if expanded_args.len() > 1 => {
+                expanded_args.remove(0);
+                exepath = expanded_args.remove(0);
             }
-            cmd.args(args);
-            cmd
+            Some(arg) if !arg.starts_with('-') => exepath = expanded_args.remove(0),
+            _ => break,
         }
     }
+    let args = expanded_args;
+
+    // a job spec on its own brings the job to the foreground, like `fg`
+    if exepath.starts_with('%') {
+        return Ok(SimpleCmd::Builtin(find_builtin("fg").unwrap(), vec![exepath], vec![]));
+    }
+    if let Some(builtin) = find_builtin(&exepath) {
+        return Ok(SimpleCmd::Builtin(builtin, args, vec![]));
+    }
+
+    let program = match search_path {
+        Some(path_var) => commands::find_program_along(path_var, &exepath),
+        None => commands::find_program(&exepath),
+    };
+    let mut cmd = match program {
+        Some(program) => Command::new(program),
+        None => return Ok(SimpleCmd::NotFound(exepath, args, search_path.map(String::from))),
+    };
+    cmd.arg0(&exepath);
+    // children only get to see exported variables
+    cmd.env_clear().envs(variables::exported());
+    cmd.args(args);
+    Ok(SimpleCmd::Process(cmd))
 }
 
-pub fn interpret_cmd_expr(expr: &CommandExpr) -> Result<Command> {
+pub fn interpret_cmd_expr(expr: &CommandExpr) -> Result<(SimpleCmd, Vec<Redirect>)> {
     match expr {
-        CommandExpr::Type1(box simplecmd_expr) => Ok(interpret_simplecmd_expr(simplecmd_expr)),
+        CommandExpr::Type1(box simplecmd_expr) => Ok((interpret_simplecmd_expr(simplecmd_expr)?, vec![])),
         CommandExpr::Type2(box simplecmd_expr, op, filename) => {
-            let mut cmd = interpret_simplecmd_expr(simplecmd_expr);
-            match op {
-                CommandOp::RedirectIn => {
-                    let f = File::open(filename)?;
-                    cmd.stdin(Stdio::from(f));
+            let cmd = interpret_simplecmd_expr(simplecmd_expr)?;
+            let filename = match &expand_fields(filename)?[..] {
+                [filename] => filename.clone(),
+                _ => return Err(to_io_error(format!("{}: ambiguous redirect", filename))),
+            };
+            let opened = match op {
+                CommandOp::RedirectIn => File::open(&filename).map(|file| (0, file)),
+                CommandOp::RedirectOut => File::create(&filename).map(|file| (1, file)),
+            };
+            // `file: No such file or directory`, without the error number
+            let redirect = opened.map_err(|e| match e.raw_os_error() {
+                Some(errno) => to_io_error(format!("{}: {}", filename, Errno::from_i32(errno).desc())),
+                None => to_io_error(format!("{}: {}", filename, e)),
+            })?;
+            Ok((cmd, vec![redirect]))
+        }
+    }
+}
+
+/// Runs a builtin or assignment within the shell process, returning its exit status
+fn run_in_shell(cmd: SimpleCmd) -> i32 {
+    match cmd {
+        SimpleCmd::Builtin(builtin, args, assignments) => {
+            if assignments.is_empty() {
+                return builtin(&args);
+            }
+
+            variables::push_scope();
+            let status = match assignments
+                .iter()
+                .try_for_each(|assignment| variables::assign_local(assignment, true))
+            {
+                Ok(()) => builtin(&args),
+                Err(e) => {
+                    eprintln!("mysh: {}", e);
+                    1
                 }
-                CommandOp::RedirectOut => {
-                    let f = File::create(filename)?;
-                    cmd.stdout(Stdio::from(f));
+            };
+            variables::pop_scope();
+            status
+        }
+        SimpleCmd::Assign(assignments) => {
+            for assignment in assignments {
+                if let Err(e) = variables::assign(&assignment) {
+                    eprintln!("mysh: {}", e);
+                    return 1;
                 }
             }
-            Ok(cmd)
+            0
+        }
+        SimpleCmd::NotFound(name, args, path_var) => not_found(&name, &args, path_var.as_deref()),
+        SimpleCmd::Process(_) => unreachable!("processes are spawned instead"),
+    }
+}
+
+/// Set while `command_not_found_handle` runs, so a missing command within it is only reported
+static IN_NOT_FOUND_HANDLE: AtomicBool = AtomicBool::new(false);
+
+// runs `command_not_found_handle` with the name that couldn't run and its args
+// as the hook's arguments, if something by that name exists, returning its status.
+// There are no shell functions, so the hook is an alias, a builtin or a program
+// along `PATH`, the one way a user can define it being an alias or a script.
+fn run_not_found_handle(name: &str, args: &[String]) -> Option<i32> {
+    const HANDLE: &str = "command_not_found_handle";
+    if commands::resolve(HANDLE).is_none() || IN_NOT_FOUND_HANDLE.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    let words: Vec<_> = std::iter::once(name).chain(args.iter().map(String::as_str)).map(quote).collect();
+    let status = match interpret_source(&format!("{} {}", HANDLE, words.join(" "))) {
+        Ok(()) => last_status(),
+        Err(e) => {
+            eprintln!("mysh: {}: {}", HANDLE, e);
+            127
+        }
+    };
+    IN_NOT_FOUND_HANDLE.store(false, Ordering::SeqCst);
+    Some(status)
+}
+
+// reports a command that couldn't run, suggesting similar names to an interactive
+// shell, and returns 127 when it's missing or 126 when it can't be executed
+fn not_found(name: &str, args: &[String], path_var: Option<&str>) -> i32 {
+    let (reason, status) = commands::failure(name, path_var);
+    if status == 127 && !name.contains('/') {
+        if let Some(status) = run_not_found_handle(name, args) {
+            return status;
+        }
+    }
+    eprintln!("mysh: {}: {}", name, reason);
+    if status == 127 && !name.contains('/') && jobs::is_enabled() {
+        let suggestions = commands::suggestions(name, path_var);
+        if !suggestions.is_empty() {
+            eprintln!("mysh: did you mean {}?", suggestions.join(", "));
+        }
+    }
+    status
+}
+
+/// Applies the redirections to the shell's own descriptors for the duration of `f`
+fn with_redirects<T, F: FnOnce() -> T>(redirects: Vec<Redirect>, f: F) -> Result<T> {
+    io::stdout().flush()?;
+
+    let mut saved = vec![];
+    let result = redirects.iter().try_for_each(|(fd, file)| {
+        saved.push((*fd, unistd::dup(*fd).map_err(to_io_error)?));
+        unistd::dup2(file.as_raw_fd(), *fd).map(|_| ()).map_err(to_io_error)
+    });
+    let result = result.map(|_| f());
+
+    io::stdout().flush()?;
+    for (fd, copy) in saved.into_iter().rev() {
+        unistd::dup2(copy, fd).map_err(to_io_error)?;
+        unistd::close(copy).map_err(to_io_error)?;
+    }
+    result
+}
+
+/// Moves a child into the job's process group, the group it leads if `pgid` is not known yet,
+/// and hands that group the terminal for foreground jobs. Both the shell and the child
+/// do this, so neither depends on which of them gets to run first.
+fn join_process_group(pid: Pid, pgid: Option<Pid>, foreground: bool) {
+    if let Some(terminal) = jobs::terminal() {
+        let pgid = pgid.unwrap_or(pid);
+        unistd::setpgid(pid, pgid).ok();
+        if foreground {
+            unistd::tcsetpgrp(terminal, pgid).ok();
         }
     }
 }
 
-pub fn interpret_job_expr(expr: &JobExpr) -> Result<Vec<u32>> {
-    let mut stdio = Stdio::inherit();
-    let mut vec = vec![];
+fn spawn(
+    cmd: SimpleCmd,
+    stdin: Option<File>,
+    stdout: Option<File>,
+    redirects: Vec<Redirect>,
+    pgid: Option<Pid>,
+    foreground: bool,
+) -> Result<Pid> {
+    let pipes = stdin.map(|f| (0, f)).into_iter().chain(stdout.map(|f| (1, f)));
 
+    match cmd {
+        SimpleCmd::Process(mut cmd) => {
+            // redirections come after the pipes, so they take precedence
+            for (fd, file) in pipes.chain(redirects) {
+                match fd {
+                    0 => cmd.stdin(file),
+                    1 => cmd.stdout(file),
+                    _ => cmd.stderr(file),
+                };
+            }
+            unsafe {
+                cmd.pre_exec(move || {
+                    join_process_group(unistd::getpid(), pgid, foreground);
+                    disable_shell_signal_handlers();
+                    Ok(())
+                });
+            }
+            let child = Pid::from_raw(cmd.spawn()?.id() as i32);
+            join_process_group(child, pgid, foreground);
+            Ok(child)
+        }
+        cmd => {
+            // builtins within a pipeline run in a forked copy of the shell
+            io::stdout().flush()?;
+            match unistd::fork().map_err(to_io_error)? {
+                ForkResult::Parent { child } => {
+                    join_process_group(child, pgid, foreground);
+                    Ok(child)
+                }
+                ForkResult::Child => {
+                    join_process_group(unistd::getpid(), pgid, foreground);
+                    // the copy is a subshell, `exit` in it leaves only the copy
+                    traps::enter_subshell();
+                    jobs::enter_subshell();
+                    for (fd, file) in pipes.chain(redirects) {
+                        unistd::dup2(file.as_raw_fd(), fd).expect("Failed to redirect");
+                    }
+                    let status = run_in_shell(cmd);
+                    io::stdout().flush().ok();
+                    process::exit(status);
+                }
+            }
+        }
+    }
+}
+
+/// Starts the processes of a pipeline, returning the job made of them.
+/// A builtin on its own runs in the shell and leaves no job behind.
+pub fn interpret_job_expr(expr: &JobExpr, background: bool) -> Result<Option<Job>> {
+    let mut cmd_exprs = vec![];
     let mut inner_job_expr = expr;
     loop {
         match inner_job_expr {
-            JobExpr::Type1(box lhs_cmd_expr) => {
-                let child = interpret_cmd_expr(lhs_cmd_expr)?.stdin(stdio).spawn()?;
-                vec.push(child.id());
-                return Ok(vec);
-            }
-            JobExpr::Type2(box lhs_cmd_expr, JobOp::Pipe, box rhs_job_expr) => {
-                let child = interpret_cmd_expr(lhs_cmd_expr)?
-                    .stdin(stdio)
-                    .stdout(Stdio::piped())
-                    .spawn()?;
-                vec.push(child.id());
-                stdio = Stdio::from(child.stdout.unwrap());
-
+            JobExpr::Type1(box cmd_expr) => {
+                cmd_exprs.push(cmd_expr);
+                break;
+            }
+            JobExpr::Type2(box cmd_expr, JobOp::Pipe, box rhs_job_expr) => {
+                cmd_exprs.push(cmd_expr);
                 inner_job_expr = rhs_job_expr;
             }
+        }
+    }
+
+    let mut pids = vec![];
+    let mut pgid = None;
+    let mut stdin = None;
+    let count = cmd_exprs.len();
+    for (i, cmd_expr) in cmd_exprs.into_iter().enumerate() {
+        let (cmd, redirects) = interpret_cmd_expr(cmd_expr)?;
+
+        // a builtin on its own runs in the shell, so it can change the shell's state
+        if count == 1 && !matches!(cmd, SimpleCmd::Process(_)) {
+            let status = with_redirects(redirects, || run_in_shell(cmd))?;
+            set_last_status(status);
+            set_pipestatus(&[status]);
+            return Ok(None);
+        }
+
+        let (next_stdin, stdout) = if i + 1 < count {
+            let (read_fd, write_fd) = unistd::pipe2(OFlag::O_CLOEXEC).map_err(to_io_error)?;
+            unsafe { (Some(File::from_raw_fd(read_fd)), Some(File::from_raw_fd(write_fd))) }
+        } else {
+            (None, None)
         };
-    };
+
+        let pid = spawn(cmd, stdin.take(), stdout, redirects, pgid, !background)?;
+        // the first process of the pipeline leads its process group
+        if jobs::is_enabled() && pgid.is_none() {
+            pgid = Some(pid);
+        }
+        pids.push(pid);
+        stdin = next_stdin;
+    }
+    Ok(Some(Job::new(pgid, pids, expr.to_string())))
+}
+
+/// Runs a job, waiting for it unless it goes in the background
+fn run_job(expr: &JobExpr, background: bool) -> Result<()> {
+    // a child may have taken the terminal before failing to start
+    let job = interpret_job_expr(expr, background).inspect_err(|_| jobs::reclaim_terminal())?;
+    match job {
+        Some(job) if background => {
+            let pid = job.processes.last().map(|&(pid, _)| pid);
+            let id = jobs::add(job);
+            if let (true, Some(pid)) = (jobs::is_enabled(), pid) {
+                eprintln!("[{}] {}", id, pid);
+            }
+            set_last_status(0);
+        }
+        // the status of a pipeline is the status of its last command
+        Some(job) => set_last_status(jobs::run_foreground(job)),
+        None => (),
+    }
+    Ok(())
+}
+
+/// Leaves the shell with the given exit status, running the `EXIT` trap first.
+/// An interactive shell hangs up on the jobs it leaves behind.
+pub fn exit_shell(status: i32) -> ! {
+    set_last_status(status);
+    traps::run(Condition::Exit);
+    if jobs::is_enabled() {
+        jobs::hangup();
+    }
+    io::stdout().flush().ok();
+    process::exit(status)
 }
 
 pub fn interpret_cmdline_expr(expr: &CommandLineExpr) -> Result<()> {
-    let fn_wait_for_job = |job: Vec<u32>| -> Result<()> {
+    let (job_expr, op) = match expr {
+        CommandLineExpr::Type1(box job_expr) => (job_expr, None),
+        CommandLineExpr::Type2(box job_expr, op) | CommandLineExpr::Type3(box job_expr, op, _) => (job_expr, Some(op)),
+    };
+    traps::run(Condition::Debug);
+    // a job that fails to start, over a redirection or an expansion, fails with status 1
+    let result = run_job(job_expr, op == Some(&CommandLineOp::Background));
+    if let Err(e) = &result {
+        eprintln!("mysh: {}", e);
+        set_last_status(1);
+        set_pipestatus(&[1]);
+    }
+    traps::run_pending();
 
-        let mut result: Result<()> = Ok(());
+    // a failing job runs the `ERR` trap and under `set -e` ends the shell,
+    // unless it is the condition of an and-or list
+    let is_condition = matches!(op, Some(CommandLineOp::And) | Some(CommandLineOp::Or));
+    if !is_condition && last_status() != 0 {
+        traps::run(Condition::Err);
+        if options::is_set("errexit") {
+            exit_shell(last_status());
+        }
+    }
 
-        job.iter().for_each(|id| {
-            let pid = Pid::from_raw(*id as i32);
-            if nix::sys::wait::waitpid(pid, None).is_err() {
-                result = Err(Error::from(ErrorKind::Other));
-            }
-        });
+    // a word that can't be expanded abandons the rest of the command line
+    if let Some(message) = result.as_ref().err().and_then(expansion_error) {
+        if is_unbound_error(message) && !jobs::is_enabled() {
+            exit_shell(1);
+        }
+        return Ok(());
+    }
 
-        result
+    let (mut op, mut cmdline_expr) = match expr {
+        CommandLineExpr::Type3(_, op, box cmdline_expr) => (op, cmdline_expr),
+        _ => return Ok(()),
     };
+    // skip the jobs of an and-or list whose condition doesn't hold
+    loop {
+        let condition = match op {
+            CommandLineOp::And => last_status() == 0,
+            CommandLineOp::Or => last_status() != 0,
+            _ => true,
+        };
+        if condition {
+            return interpret_cmdline_expr(cmdline_expr);
+        }
+        match cmdline_expr {
+            CommandLineExpr::Type3(_, next_op, box next_cmdline_expr) => {
+                op = next_op;
+                cmdline_expr = next_cmdline_expr;
+            }
+            _ => return Ok(()),
+        }
+    }
+}
 
-    match expr {
-        CommandLineExpr::Type1(box job_expr)
-        | CommandLineExpr::Type2(box job_expr, CommandLineOp::Sequence) => {
-            interpret_job_expr(job_expr).and_then(fn_wait_for_job)
+pub fn interpret(expr: &CommandLineExpr) -> Result<()> {
+    interpret_cmdline_expr(expr)
+}
+
+/// Parses and runs a complete piece of shell source, such as the command of a trap
+pub fn interpret_source(source: &str) -> Result<()> {
+    let tokens = source.tokenize().map_err(|e| to_io_error(e.to_string()))?.expand_aliases();
+    match tokens.get_stream().parse().map_err(|e| to_io_error(e.to_string()))? {
+        Some(expr) => interpret(&expr),
+        None => Ok(()),
+    }
+}
+
+/// How the commands `interpret_script` reads were given to the shell
+#[derive(Clone, Copy, PartialEq)]
+pub enum Reading {
+    /// Typed at a prompt, a syntax error only dropping the command it is in
+    Interactive,
+    /// The shell's own script, `-c` string or standard input, a syntax error ending the shell
+    Script,
+    /// A file or string run by `source`, a syntax error ending it with the error returned
+    Sourced,
+}
+
+/// Reads commands, running each one as soon as it is complete. A command failing to
+/// run is reported and reading goes on. Only sourced commands return, at the end of
+/// their input; otherwise the shell exits there.
+pub fn interpret_script(reader: &mut dyn io::BufRead, reading: Reading) -> Result<()> {
+    let interactive = reading == Reading::Interactive;
+    let debug_print = std::env::var("DEBUG_PRINT").is_ok();
+    // lines read so far for a command that is not complete yet
+    let mut input = String::new();
+
+    loop {
+        if input.is_empty() && reading != Reading::Sourced {
+            traps::run_pending();
+            jobs::notify();
         }
-        CommandLineExpr::Type2(box job_expr, CommandLineOp::Background) => {
-            interpret_job_expr(job_expr).map(|_| ())
+        if interactive {
+            if input.is_empty() {
+                print!("{}", get_prompt());
+            } else {
+                print!("{}", get_continuation_prompt());
+            }
+            io::stdout().flush().expect("Failed to flush");
         }
-        CommandLineExpr::Type3(box job_expr, op, box cmdline_expr) => {
-            match op {
-                CommandLineOp::Background => {
-                    interpret_job_expr(job_expr)?;
+
+        let start = input.len();
+        let read = match reader.read_line(&mut input) {
+            Ok(read) => read,
+            Err(e) if reading == Reading::Sourced => return Err(e),
+            // a read error ends the input too
+            Err(_) => 0,
+        };
+        if read == 0 {
+            if !input.is_empty() {
+                let error = to_io_error("syntax error: unexpected end of file");
+                if reading == Reading::Sourced {
+                    return Err(error);
                 }
-                CommandLineOp::Sequence => {
-                    interpret_job_expr(job_expr)
-                        .and_then(fn_wait_for_job)?;
+                eprintln!("mysh: {}", error);
+                if !interactive {
+                    exit_shell(2);
                 }
             }
+            if reading == Reading::Sourced {
+                return Ok(());
+            }
+            // returns only after warning about stopped jobs
+            crate::builtin::exit(&[]);
+            input.clear();
+            continue;
+        }
+        if interactive && start == 0 {
+            erase_right_prompt(&input);
+        }
+        if options::is_set("verbose") {
+            eprint!("{}", &input[start..]);
+        }
 
-            interpret_cmdline_expr(cmdline_expr)
+        let parsed = input.as_str().tokenize().map(Tokens::expand_aliases).and_then(|tokens| {
+            if debug_print {
+                println!("Tokens: {:?}", tokens);
+            }
+            tokens.get_stream().parse()
+        });
+        match parsed {
+            Ok(Some(expr)) => {
+                if debug_print {
+                    println!("Syntax Tree: \n{:#?}\n", expr);
+                }
+                let started = std::time::Instant::now();
+                if let Err(e) = interpret(&expr) {
+                    eprintln!("mysh: {}", e);
+                }
+                if reading != Reading::Sourced {
+                    set_last_duration(started.elapsed());
+                }
+            }
+            Ok(None) => (),
+            Err(ParseError::Incomplete(_)) => continue,
+            Err(e) if reading == Reading::Sourced => return Err(to_io_error(e.to_string())),
+            Err(e) => {
+                eprintln!("mysh: syntax error: {}", e);
+                if !interactive {
+                    exit_shell(2);
+                }
+            }
         }
+        input.clear();
     }
 }
-
-pub fn interpret(expr: &CommandLineExpr) -> Result<()> {
-    interpret_cmdline_expr(&expr)
-}
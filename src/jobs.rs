@@ -0,0 +1,683 @@
+//! Jobs module keeps track of the pipelines started by the shell
+
+use crate::interpret;
+use crate::options;
+use lazy_static::lazy_static;
+use nix::fcntl::{fcntl, FcntlArg};
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{self, Pid};
+use std::fmt;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Mutex;
+
+/// Copy of the controlling terminal's descriptor, -1 while job control is off
+static TERMINAL: AtomicI32 = AtomicI32::new(-1);
+
+/// Pid of the last process put in the background, `$!`, 0 before there is one
+static LAST_BACKGROUND: AtomicI32 = AtomicI32::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Running,
+    Stopped(Signal),
+    Exited(i32),
+    Signaled(Signal),
+}
+
+impl State {
+    /// Exit status the state amounts to, 128 plus the signal number for signals
+    pub fn status(self) -> i32 {
+        match self {
+            State::Running => 0,
+            State::Exited(code) => code,
+            State::Stopped(signal) | State::Signaled(signal) => 128 + signal as i32,
+        }
+    }
+
+    pub fn is_done(self) -> bool {
+        matches!(self, State::Exited(_) | State::Signaled(_))
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Running => write!(f, "Running"),
+            State::Stopped(_) => write!(f, "Stopped"),
+            State::Exited(0) => write!(f, "Done"),
+            State::Exited(code) => write!(f, "Exit {}", code),
+            State::Signaled(Signal::SIGHUP) => write!(f, "Hangup"),
+            State::Signaled(Signal::SIGINT) => write!(f, "Interrupt"),
+            State::Signaled(Signal::SIGKILL) => write!(f, "Killed"),
+            State::Signaled(Signal::SIGTERM) => write!(f, "Terminated"),
+            State::Signaled(signal) => write!(f, "{:?}", signal),
+        }
+    }
+}
+
+/// Pipeline started by the shell, along with the state of each of its processes
+#[derive(Clone, Debug)]
+pub struct Job {
+    /// Job number, 0 until the job is entered in the job table
+    pub id: usize,
+    /// Process group of the pipeline, `None` without job control
+    pub pgid: Option<Pid>,
+    pub processes: Vec<(Pid, State)>,
+    pub command: String,
+    /// Whether the job was put in the background
+    pub background: bool,
+    /// Whether the user has been told about the job's latest state
+    pub notified: bool,
+    /// Whether `disown -h` spared the job the hangup sent when the shell exits
+    pub nohup: bool,
+}
+
+impl Job {
+    pub fn new(pgid: Option<Pid>, pids: Vec<Pid>, command: String) -> Job {
+        Job {
+            id: 0,
+            pgid,
+            processes: pids.into_iter().map(|pid| (pid, State::Running)).collect(),
+            command,
+            background: false,
+            notified: true,
+            nohup: false,
+        }
+    }
+
+    /// A job runs while any of its processes runs and is stopped while any is stopped,
+    /// otherwise it is done with the state of its last process
+    pub fn state(&self) -> State {
+        let mut states = self.processes.iter().map(|&(_, state)| state);
+        if states.clone().any(|state| state == State::Running) {
+            State::Running
+        } else if let Some(state) = states.clone().find(|state| matches!(state, State::Stopped(_))) {
+            state
+        } else {
+            states.next_back().unwrap_or(State::Exited(0))
+        }
+    }
+
+    /// Exit status of the job, that of its last process. With `pipefail` it is
+    /// the status of the last process that failed, 0 if none did.
+    pub fn status(&self) -> i32 {
+        let state = self.state();
+        if !state.is_done() || !options::is_set("pipefail") {
+            return state.status();
+        }
+        self.processes.iter().rev().map(|&(_, state)| state.status()).find(|&status| status != 0).unwrap_or(0)
+    }
+
+    /// Process id standing for the whole job
+    pub fn leader(&self) -> Option<Pid> {
+        self.pgid.or_else(|| self.processes.first().map(|&(pid, _)| pid))
+    }
+
+    /// Sends a signal to every process of the job
+    pub fn signal<T: Into<Option<Signal>> + Copy>(&self, signal: T) -> nix::Result<()> {
+        match self.pgid {
+            // a negative pid signals the whole process group
+            Some(pgid) => signal::kill(Pid::from_raw(-pgid.as_raw()), signal),
+            None => self
+                .processes
+                .iter()
+                .filter(|(_, state)| !state.is_done())
+                .try_for_each(|&(pid, _)| signal::kill(pid, signal)),
+        }
+    }
+
+    fn resume(&mut self) -> nix::Result<()> {
+        self.signal(Signal::SIGCONT)?;
+        for (_, state) in &mut self.processes {
+            if let State::Stopped(_) = state {
+                *state = State::Running;
+            }
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, status: WaitStatus) {
+        let (pid, state) = match status {
+            WaitStatus::Exited(pid, code) => (pid, State::Exited(code)),
+            WaitStatus::Signaled(pid, signal, _) => (pid, State::Signaled(signal)),
+            WaitStatus::Stopped(pid, signal) => (pid, State::Stopped(signal)),
+            WaitStatus::Continued(pid) => (pid, State::Running),
+            _ => return,
+        };
+        if let Some(process) = self.processes.iter_mut().find(|(p, _)| *p == pid) {
+            process.1 = state;
+        }
+    }
+
+    /// Collects status changes of the job's processes, blocking unless `WNOHANG` is given
+    fn wait(&mut self, flags: WaitPidFlag) {
+        let blocking = !flags.contains(WaitPidFlag::WNOHANG);
+        for i in 0..self.processes.len() {
+            let (pid, state) = self.processes[i];
+            // a stopped process has nothing more to report until it is continued
+            if state.is_done() || (blocking && state != State::Running) {
+                continue;
+            }
+            match waitpid(pid, Some(flags)) {
+                Ok(status) => self.update(status),
+                // somebody else reaped it, there is nothing left to learn
+                Err(_) => self.processes[i].1 = State::Exited(0),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct JobTable {
+    /// Jobs ordered by their number
+    jobs: Vec<Job>,
+    /// Job numbers from the least to the most recently started, stopped or resumed
+    recent: Vec<usize>,
+    /// Processes of disowned jobs, still reaped so they don't linger as zombies
+    disowned: Vec<Pid>,
+    /// Exit statuses of the processes of finished jobs that left the table once
+    /// reported, kept for `wait` until it asks for them
+    reaped: Vec<(Pid, i32)>,
+}
+
+impl JobTable {
+    fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+
+    fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    fn insert(&mut self, mut job: Job) -> usize {
+        if job.id == 0 {
+            job.id = self.jobs.last().map_or(1, |last| last.id + 1);
+        }
+        let id = job.id;
+        let position = self.jobs.iter().position(|job| job.id > id).unwrap_or(self.jobs.len());
+        self.jobs.insert(position, job);
+        self.touch(id);
+        id
+    }
+
+    fn remove(&mut self, id: usize) -> Option<Job> {
+        self.recent.retain(|&recent| recent != id);
+        let position = self.jobs.iter().position(|job| job.id == id)?;
+        Some(self.jobs.remove(position))
+    }
+
+    // removes a finished job, keeping the exit status of each of its processes.
+    // A job still running or stopped stays in the table.
+    fn retire(&mut self, id: usize) {
+        if !self.get(id).is_some_and(|job| job.state().is_done()) {
+            return;
+        }
+        if let Some(job) = self.remove(id) {
+            for (pid, state) in job.processes {
+                self.reaped.retain(|&(reaped, _)| reaped != pid);
+                self.reaped.push((pid, state.status()));
+            }
+        }
+        if self.reaped.len() > MAX_REAPED {
+            let excess = self.reaped.len() - MAX_REAPED;
+            self.reaped.drain(..excess);
+        }
+    }
+
+    fn touch(&mut self, id: usize) {
+        self.recent.retain(|&recent| recent != id);
+        self.recent.push(id);
+    }
+
+    /// The current job `%+` and the previous job `%-`, stopped jobs taking precedence
+    fn current(&self) -> (Option<usize>, Option<usize>) {
+        let mut order = self.recent.clone();
+        order.sort_by_key(|&id| self.get(id).is_some_and(|job| matches!(job.state(), State::Stopped(_))));
+        (order.pop(), order.pop())
+    }
+
+    fn marker(&self, id: usize) -> char {
+        match self.current() {
+            (Some(current), _) if current == id => '+',
+            (_, Some(previous)) if previous == id => '-',
+            _ => ' ',
+        }
+    }
+}
+
+/// Most exit statuses of retired processes kept at a time, the oldest going first
+const MAX_REAPED: usize = 1024;
+
+lazy_static! {
+    static ref JOBS: Mutex<JobTable> = Mutex::new(JobTable::default());
+}
+
+/// Takes control of the terminal when the shell is interactive,
+/// putting the shell in a process group of its own
+pub fn init() {
+    if !unistd::isatty(0).unwrap_or(false) {
+        return;
+    }
+
+    // wait until we are in the foreground before taking over the terminal
+    loop {
+        let pgrp = unistd::getpgrp();
+        match unistd::tcgetpgrp(0) {
+            Ok(foreground) if foreground == pgrp => break,
+            Ok(_) => {
+                signal::kill(Pid::from_raw(-pgrp.as_raw()), Signal::SIGTTIN).ok();
+            }
+            Err(_) => return,
+        }
+    }
+
+    // taking the terminal from the background would stop us otherwise
+    let ignore = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+    let previous = unsafe { signal::sigaction(Signal::SIGTTOU, &ignore).unwrap() };
+
+    let pid = unistd::getpid();
+    // a session leader already leads its own group
+    if unistd::getpgrp() != pid {
+        unistd::setpgid(pid, pid).ok();
+    }
+    if unistd::tcsetpgrp(0, pid).is_ok() {
+        if let Ok(fd) = fcntl(0, FcntlArg::F_DUPFD_CLOEXEC(10)) {
+            TERMINAL.store(fd, Ordering::SeqCst);
+        }
+    }
+    unsafe {
+        signal::sigaction(Signal::SIGTTOU, &previous).unwrap();
+    }
+}
+
+pub fn is_enabled() -> bool {
+    terminal().is_some()
+}
+
+/// Turns job control off in a forked copy of the shell, leaving the jobs to the parent,
+/// so that the copy neither touches the terminal nor waits for or hangs up on them
+pub fn enter_subshell() {
+    let fd = TERMINAL.swap(-1, Ordering::SeqCst);
+    if fd >= 0 {
+        unistd::close(fd).ok();
+    }
+    *JOBS.lock().unwrap() = JobTable::default();
+}
+
+/// Descriptor of the controlling terminal while job control is enabled
+pub fn terminal() -> Option<RawFd> {
+    Some(TERMINAL.load(Ordering::SeqCst)).filter(|&fd| fd >= 0)
+}
+
+/// Gives the terminal back to the shell after a job had it
+pub fn reclaim_terminal() {
+    if let Some(fd) = terminal() {
+        unistd::tcsetpgrp(fd, unistd::getpgrp()).ok();
+    }
+}
+
+/// Enters a background job in the job table, returning its job number
+pub fn add(mut job: Job) -> usize {
+    job.background = true;
+    if let Some(&(pid, _)) = job.processes.last() {
+        LAST_BACKGROUND.store(pid.as_raw(), Ordering::SeqCst);
+    }
+    let mut table = JOBS.lock().unwrap();
+    // a reused pid no longer stands for the process that finished
+    table.reaped.retain(|(pid, _)| !job.processes.iter().any(|(p, _)| p == pid));
+    table.insert(job)
+}
+
+/// Pid of the last process put in the background
+pub fn last_background_pid() -> Option<Pid> {
+    Some(LAST_BACKGROUND.load(Ordering::SeqCst)).filter(|&pid| pid != 0).map(Pid::from_raw)
+}
+
+/// Removes a finished job from the table once it has been reported,
+/// keeping the exit statuses of its processes for `wait`
+pub fn retire(id: usize) {
+    JOBS.lock().unwrap().retire(id);
+}
+
+/// Exit status of a process of a retired job, forgotten once it is asked for
+pub fn take_reaped(pid: Pid) -> Option<i32> {
+    let mut table = JOBS.lock().unwrap();
+    let position = table.reaped.iter().position(|&(reaped, _)| reaped == pid)?;
+    Some(table.reaped.remove(position).1)
+}
+
+/// Snapshot of the job table, along with the `+`/`-` marker of each job
+pub fn list() -> Vec<(Job, char)> {
+    let table = JOBS.lock().unwrap();
+    table.jobs.iter().map(|job| (job.clone(), table.marker(job.id))).collect()
+}
+
+/// Polls the jobs in the table for processes that finished, stopped or continued
+pub fn update() {
+    let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+    let mut table = JOBS.lock().unwrap();
+    for job in &mut table.jobs {
+        let before = job.state();
+        job.wait(flags);
+        if job.state() != before {
+            job.notified = false;
+        }
+    }
+    table
+        .disowned
+        .retain(|&pid| waitpid(pid, Some(WaitPidFlag::WNOHANG)) == Ok(WaitStatus::StillAlive));
+}
+
+/// Reaps jobs that changed state in the background, reporting them before the next prompt
+/// when the shell is interactive. Finished jobs leave the job table once reported, without
+/// job control they stay until `wait` collects their status.
+pub fn notify() {
+    update();
+    let mut table = JOBS.lock().unwrap();
+    let mut finished = vec![];
+    for i in 0..table.jobs.len() {
+        let job = &table.jobs[i];
+        let state = job.state();
+        if job.notified || state == State::Running {
+            continue;
+        }
+        if is_enabled() {
+            eprintln!("{}", format(job, table.marker(job.id), false));
+            if state.is_done() {
+                finished.push(job.id);
+            }
+        }
+        table.jobs[i].notified = true;
+    }
+    for id in finished {
+        table.retire(id);
+    }
+}
+
+/// Sends a signal to a job in the table. A stopped job is continued after
+/// `SIGTERM` or `SIGHUP`, otherwise it wouldn't act on them until resumed.
+pub fn signal_job(id: usize, signal: Option<Signal>) -> nix::Result<()> {
+    let mut table = JOBS.lock().unwrap();
+    let job = match table.get_mut(id) {
+        Some(job) => job,
+        None => return Ok(()),
+    };
+    job.signal(signal)?;
+    let stopped = matches!(job.state(), State::Stopped(_));
+    if stopped && matches!(signal, Some(Signal::SIGTERM) | Some(Signal::SIGHUP)) {
+        job.resume()?;
+    }
+    Ok(())
+}
+
+/// Removes a job from the table without waiting for it, so it is neither
+/// reported nor hung up on when the shell exits
+pub fn disown(id: usize) -> Option<Job> {
+    let mut table = JOBS.lock().unwrap();
+    let job = table.remove(id)?;
+    let running = job.processes.iter().filter(|(_, state)| !state.is_done());
+    table.disowned.extend(running.map(|&(pid, _)| pid));
+    Some(job)
+}
+
+/// Keeps the hangup sent when the shell exits from reaching the job
+pub fn set_nohup(id: usize) {
+    if let Some(job) = JOBS.lock().unwrap().get_mut(id) {
+        job.nohup = true;
+    }
+}
+
+/// Sends `SIGHUP` to the jobs left when an interactive shell exits, continuing stopped ones
+pub fn hangup() {
+    let ids: Vec<_> = list().into_iter().filter(|(job, _)| !job.nohup).map(|(job, _)| job.id).collect();
+    for id in ids {
+        signal_job(id, Some(Signal::SIGHUP)).ok();
+    }
+}
+
+/// Number of the job the process belongs to
+pub fn find_pid(pid: Pid) -> Option<usize> {
+    let table = JOBS.lock().unwrap();
+    table.jobs.iter().find(|job| job.processes.iter().any(|&(p, _)| p == pid)).map(|job| job.id)
+}
+
+/// Waits for a job in the table to finish or stop, returning the exit status of
+/// the job or of one of its processes. Finished jobs leave the table unreported.
+pub fn wait_job(id: usize, pid: Option<Pid>) -> Option<i32> {
+    let mut job = JOBS.lock().unwrap().remove(id)?;
+    while job.state() == State::Running {
+        job.wait(WaitPidFlag::WUNTRACED);
+    }
+
+    let status = match pid {
+        Some(pid) => job.processes.iter().find(|&&(p, _)| p == pid).map(|&(_, state)| state.status())?,
+        None => job.status(),
+    };
+    if !job.state().is_done() {
+        JOBS.lock().unwrap().insert(job);
+    }
+    Some(status)
+}
+
+/// Waits for the next job in the table to finish, returning its exit status,
+/// or `None` if there is no running job to wait for
+pub fn wait_any() -> Option<i32> {
+    loop {
+        let mut table = JOBS.lock().unwrap();
+        if let Some(id) = table.jobs.iter().find(|job| job.state().is_done()).map(|job| job.id) {
+            return table.remove(id).map(|job| job.status());
+        }
+        if !table.jobs.iter().any(|job| job.state() == State::Running) {
+            return None;
+        }
+        drop(table);
+
+        // any child will do, the status goes to whichever job it belongs to
+        let status = waitpid(Pid::from_raw(-1), None).ok()?;
+        for job in &mut JOBS.lock().unwrap().jobs {
+            job.update(status);
+        }
+    }
+}
+
+/// Formats a job the way `jobs` lists it, with the leader's pid if `long` is set
+pub fn format(job: &Job, marker: char, long: bool) -> String {
+    let suffix = if job.background && job.state() == State::Running { " &" } else { "" };
+    let state = job.state().to_string();
+    match job.leader() {
+        Some(pid) if long => format!("[{}]{} {} {:<24}{}{}", job.id, marker, pid, state, job.command, suffix),
+        _ => format!("[{}]{}  {:<24}{}{}", job.id, marker, state, job.command, suffix),
+    }
+}
+
+/// Resolves a job spec such as `%1`, `%%`, `%-`, `%name` or `%?text` to a job number
+pub fn find(spec: &str) -> Result<usize, String> {
+    let table = JOBS.lock().unwrap();
+    let (current, previous) = table.current();
+    let name = spec.strip_prefix('%').unwrap_or(spec);
+
+    let matching = |pattern: &dyn Fn(&str) -> bool| {
+        let ids: Vec<_> = table.jobs.iter().filter(|job| pattern(&job.command)).map(|job| job.id).collect();
+        match &ids[..] {
+            [id] => Ok(Some(*id)),
+            [] => Ok(None),
+            _ => Err(format!("{}: ambiguous job spec", name)),
+        }
+    };
+
+    let found = match name {
+        "" | "%" | "+" => return current.ok_or_else(|| "current: no such job".to_string()),
+        "-" => return previous.ok_or_else(|| "previous: no such job".to_string()),
+        _ if name.chars().all(|ch| ch.is_ascii_digit()) => {
+            name.parse().ok().filter(|&id| table.get(id).is_some())
+        }
+        _ if name.starts_with('?') => matching(&|command| command.contains(&name[1..]))?,
+        _ => matching(&|command| command.starts_with(name))?,
+    };
+
+    found.ok_or_else(|| format!("{}: no such job", spec))
+}
+
+fn foreground(mut job: Job, resume: bool) -> i32 {
+    job.background = false;
+    if let (Some(fd), Some(pgid)) = (terminal(), job.pgid) {
+        unistd::tcsetpgrp(fd, pgid).ok();
+    }
+    if resume {
+        job.resume().ok();
+    }
+
+    while job.state() == State::Running {
+        job.wait(WaitPidFlag::WUNTRACED);
+    }
+
+    reclaim_terminal();
+
+    let statuses: Vec<_> = job.processes.iter().map(|&(_, state)| state.status()).collect();
+    interpret::set_pipestatus(&statuses);
+    let status = job.status();
+    if let State::Stopped(_) = job.state() {
+        job.notified = true;
+        let mut table = JOBS.lock().unwrap();
+        let id = table.insert(job);
+        let job = table.get(id).unwrap();
+        eprintln!("\n{}", format(job, table.marker(id), false));
+    }
+    status
+}
+
+/// Waits for a job started in the foreground until it finishes or stops,
+/// returning its exit status. Stopped jobs are entered in the job table.
+pub fn run_foreground(job: Job) -> i32 {
+    foreground(job, false)
+}
+
+/// Continues a job with `SIGCONT`, in the foreground like `fg` or in the background like `bg`
+pub fn continue_job(id: usize, in_foreground: bool) -> Result<i32, String> {
+    let mut table = JOBS.lock().unwrap();
+    if in_foreground {
+        let job = table.remove(id).ok_or_else(|| format!("%{}: no such job", id))?;
+        drop(table);
+        println!("{}", job.command);
+        return Ok(foreground(job, true));
+    }
+
+    let marker = table.marker(id);
+    let job = table.get_mut(id).ok_or_else(|| format!("%{}: no such job", id))?;
+    if job.state() == State::Running {
+        return Err(format!("job {} already in background", id));
+    }
+    job.resume().map_err(|e| e.to_string())?;
+    job.background = true;
+    println!("[{}]{} {} &", id, marker, job.command);
+    table.touch(id);
+    Ok(0)
+}
+
+#[test]
+fn test_job_table() {
+    let mut table = JobTable::default();
+    let sleep = Job::new(None, vec![Pid::from_raw(1 << 22)], "sleep 5".to_string());
+    let mut vim = Job::new(None, vec![Pid::from_raw(1 << 22)], "vim notes".to_string());
+    vim.processes[0].1 = State::Stopped(Signal::SIGTSTP);
+
+    assert_eq!(table.insert(vim), 1);
+    assert_eq!(table.insert(sleep.clone()), 2);
+    // stopped jobs are preferred as the current job
+    assert_eq!(table.current(), (Some(1), Some(2)));
+    assert_eq!(format(table.get(1).unwrap(), '+', false), "[1]+  Stopped                 vim notes");
+
+    table.remove(1);
+    assert_eq!(table.insert(sleep), 3);
+    assert_eq!(table.current(), (Some(3), Some(2)));
+    assert_eq!(table.marker(2), '-');
+}
+
+// starts `sh -c 'exit code'`, left for the job table to reap
+#[cfg(test)]
+#[allow(clippy::zombie_processes)]
+fn spawn_exit(code: i32) -> Pid {
+    let child = std::process::Command::new("sh").args(["-c", &format!("exit {}", code)]).spawn().unwrap();
+    Pid::from_raw(child.id() as i32)
+}
+
+// blocks until the child has exited, leaving it for the job table to reap
+#[cfg(test)]
+fn wait_exited(pid: Pid) {
+    use nix::libc;
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let flags = libc::WEXITED | libc::WNOWAIT;
+    assert_eq!(unsafe { libc::waitid(libc::P_PID, pid.as_raw() as libc::id_t, &mut info, flags) }, 0);
+}
+
+#[test]
+fn test_run_foreground() {
+    let job = Job::new(None, vec![spawn_exit(3)], "sh -c 'exit 3'".to_string());
+    assert_eq!(run_foreground(job), 3);
+}
+
+#[test]
+fn test_wait_job() {
+    let (first, last) = (spawn_exit(4), spawn_exit(5));
+    let id = add(Job::new(None, vec![first, last], "sh | sh".to_string()));
+    assert_eq!(find_pid(first), Some(id));
+    assert_eq!(wait_job(id, Some(first)), Some(4));
+    // the finished job is gone from the table
+    assert_eq!(find_pid(last), None);
+    assert_eq!(wait_job(id, None), None);
+}
+
+#[test]
+fn test_disown() {
+    let pid = spawn_exit(0);
+    let id = add(Job::new(None, vec![pid], "sh".to_string()));
+    set_nohup(id);
+    assert!(list().iter().any(|(job, _)| job.id == id && job.nohup));
+    assert!(disown(id).is_some());
+    assert_eq!(find_pid(pid), None);
+    // the disowned process is still reaped
+    wait_exited(pid);
+    update();
+    assert!(!JOBS.lock().unwrap().disowned.contains(&pid));
+}
+
+#[test]
+fn test_retire() {
+    let mut table = JobTable::default();
+    let mut job = Job::new(None, vec![Pid::from_raw(1 << 22), Pid::from_raw((1 << 22) + 1)], "a | b".to_string());
+    job.processes[0].1 = State::Exited(3);
+    job.processes[1].1 = State::Signaled(Signal::SIGTERM);
+    let id = table.insert(job);
+    table.retire(id);
+    assert!(table.get(id).is_none());
+    assert_eq!(table.reaped, vec![(Pid::from_raw(1 << 22), 3), (Pid::from_raw((1 << 22) + 1), 143)]);
+
+    // a job that is still running isn't retired
+    let id = table.insert(Job::new(None, vec![Pid::from_raw((1 << 22) + 2)], "c".to_string()));
+    table.retire(id);
+    assert!(table.get(id).is_some());
+    assert_eq!(table.reaped.len(), 2);
+}
+
+#[test]
+fn test_wait_reaped() {
+    // without job control a finished job stays in the table until waited for
+    let pid = spawn_exit(6);
+    let id = add(Job::new(None, vec![pid], "sh".to_string()));
+    wait_exited(pid);
+    notify();
+    assert_eq!(find_pid(pid), Some(id));
+    assert_eq!(wait_job(id, Some(pid)), Some(6));
+
+    // once reported and retired only its status is left
+    let pid = spawn_exit(7);
+    let id = add(Job::new(None, vec![pid], "sh".to_string()));
+    wait_exited(pid);
+    update();
+    retire(id);
+    assert_eq!(find_pid(pid), None);
+    assert_eq!(take_reaped(pid), Some(7));
+    assert_eq!(take_reaped(pid), None);
+}
@@ -1,6 +1,8 @@
 //! Lexer module is responsible for splitting a string into tokens
 
+use crate::aliases;
 use crate::parser::*;
+use std::borrow::Cow;
 use std::{collections::VecDeque, fmt, mem};
 
 fn try_extract_symbol_at_start(line: &str) -> Option<&'static str> {
@@ -19,7 +21,7 @@ fn try_extract_symbol_at_start(line: &str) -> Option<&'static str> {
 pub struct Tokens<'a> (VecDeque<Token<'a>>);
 
 pub trait Tokenizer {
-    fn tokenize(&self) -> Result<Tokens<'_>, String>;
+    fn tokenize(&self) -> Result<Tokens<'_>, ParseError>;
 }
 
 impl fmt::Debug for Tokens<'_> {
@@ -38,7 +40,7 @@ impl Tokens<'_> {
         // turn Token::QuotedString into Token::VarString
         for tok in &mut self.0 {
             if let Token::QuotedString(_) = *tok {
-                let quoted_token = mem::replace(tok, Token::default());
+                let quoted_token = mem::take(tok);
                 if let Token::QuotedString(s) = quoted_token {
                     *tok = Token::VarString(s);
                 }
@@ -62,11 +64,75 @@ impl Tokens<'_> {
             }
         }
 
-        // remove newline and whitespace
-        self.0
-            .retain(|tok| *tok != Token::WhiteSpace && *tok != Token::Symbol("\n"));
+        // remove whitespace and what is left of line continuations. A newline ends the
+        // command before it like `;` does, unless the line ended in an operator already.
+        let mut tokens = VecDeque::with_capacity(self.0.len());
+        for tok in self.0.drain(..) {
+            match tok {
+                Token::WhiteSpace => (),
+                Token::VarString(ref s) if s.is_empty() => (),
+                Token::Symbol("\n") => {
+                    if let Some(Token::VarString(_)) = tokens.back() {
+                        tokens.push_back(Token::Symbol(";"));
+                    }
+                }
+                tok => tokens.push_back(tok),
+            }
+        }
 
-        self
+        Tokens(tokens)
+    }
+
+    /// Replaces aliases used as the first word of a simple command with their value.
+    /// The value is expanded in turn, except for aliases already being expanded, and
+    /// when it ends in a blank the word after it is checked for an alias too.
+    pub fn expand_aliases(self) -> Self {
+        // each token along with the aliases it came from and whether it may be an alias
+        let mut queue: VecDeque<_> = self.0.into_iter().map(|tok| (tok, vec![], false)).collect();
+        let mut tokens = VecDeque::with_capacity(queue.len());
+        let mut command_position = true;
+        let mut redirect_target = false;
+        let mut alias_ended_command = false;
+
+        while let Some((tok, active, check)) = queue.pop_front() {
+            if let Token::VarString(word) = &tok {
+                let expandable = (command_position || check) && !redirect_target;
+                let active_alias = active.iter().any(|name: &String| name.as_str() == word.as_ref());
+                if let Some(value) = aliases::get(word).filter(|_| expandable && !active_alias) {
+                    // a value that doesn't lex leaves the word alone
+                    if let Ok(expansion) = value.as_str().tokenize() {
+                        if value.ends_with([' ', '\t']) {
+                            if let Some(next) = queue.front_mut() {
+                                next.2 = true;
+                            }
+                        }
+                        let mut active = active.clone();
+                        active.push(word.to_string());
+                        for tok in expansion.0.into_iter().rev() {
+                            queue.push_front((into_owned(tok), active.clone(), false));
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            // a value ending the command leaves nothing for the `;` of a newline to end
+            let ends_command = matches!(tok, Token::Symbol(";") | Token::Symbol("&"));
+            if tok == Token::Symbol(";") && mem::replace(&mut alias_ended_command, false) {
+                continue;
+            }
+            alias_ended_command = ends_command && !active.is_empty();
+
+            match tok {
+                Token::Symbol(symbol) if ["<", ">", ">>", "<<"].contains(&symbol) => redirect_target = true,
+                Token::Symbol(_) => command_position = true,
+                _ if redirect_target => redirect_target = false,
+                // assignments before the command leave the next word in command position
+                ref tok => command_position &= tok.is_assignment(),
+            }
+            tokens.push_back(tok);
+        }
+        Tokens(tokens)
     }
 
     pub fn get_stream(&self) -> impl TokenStream<'_>{
@@ -74,33 +140,119 @@ impl Tokens<'_> {
     }
 }
 
+// a token no longer borrowing the string it was read from
+fn into_owned(tok: Token<'_>) -> Token<'static> {
+    match tok {
+        Token::WhiteSpace => Token::WhiteSpace,
+        Token::Symbol(symbol) => Token::Symbol(symbol),
+        Token::QuotedString(s) => Token::QuotedString(Cow::Owned(s.into_owned())),
+        Token::VarString(s) => Token::VarString(Cow::Owned(s.into_owned())),
+    }
+}
+
+// length of the `(...)` at the start of `s`, skipping over quoted parentheses
+fn parenthesized_len(s: &str) -> Option<usize> {
+    let mut it = s.char_indices().skip(1);
+    while let Some((i, ch)) = it.next() {
+        match ch {
+            ')' => return Some(i + 1),
+            '"' | '\'' => {
+                let end = i + quoted_len(&s[i..])?;
+                it.by_ref().take_while(|&(j, _)| j + 1 < end).for_each(drop);
+            }
+            '\\' => {
+                it.next();
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// length of the quoted string at the start of `s`, including both quotes
+fn quoted_len(s: &str) -> Option<usize> {
+    let quote = s.chars().next()?;
+    let mut it = s.char_indices().skip(1);
+    while let Some((i, ch)) = it.next() {
+        match ch {
+            '\\' if quote == '"' => {
+                it.next();
+            }
+            _ if ch == quote => return Some(i + 1),
+            _ => (),
+        }
+    }
+    None
+}
+
 impl Tokenizer for str {
-    fn tokenize(&self) -> Result<Tokens<'_>, String> {
+    fn tokenize(&self) -> Result<Tokens<'_>, ParseError> {
         let mut tokens: VecDeque<Token<'_>> = VecDeque::new();
-        let mut it = self.chars().enumerate().peekable();
+        let mut it = self.char_indices().peekable();
 
         let mut start = 0;
         let mut capture_state = false;
 
+        // words are kept verbatim, quote removal is left to expansion
         while let Some((i, ch)) = it.next() {
             let current_token = match ch {
-                '\\' => {
-                    if let Some((_, ch)) = it.next() {
-                        Some(Token::VarString(ch.to_string().into()))
-                    } else {
-                        None
+                // line continuation, joining the lines into one
+                '\\' if self[i..].starts_with("\\\n") => {
+                    it.next();
+                    if it.peek().is_none() {
+                        return Err(ParseError::Incomplete("unexpected end of file".to_string()));
+                    }
+                    Some(Token::VarString("".into()))
+                }
+                // comment until the end of the line, only at the start of a word
+                '#' if !capture_state
+                    && matches!(tokens.back(), None | Some(Token::WhiteSpace) | Some(Token::Symbol(_))) =>
+                {
+                    while it.peek().is_some_and(|&(_, ch)| ch != '\n') {
+                        it.next();
                     }
+                    Some(Token::WhiteSpace)
+                }
+                '\\' => {
+                    let end = it.next().map_or(self.len(), |(j, ch)| j + ch.len_utf8());
+                    Some(Token::VarString((&self[i..end]).into()))
                 }
                 '\t' | ' ' => Some(Token::WhiteSpace),
                 '"' | '\'' => {
-                    // extract string literal in between quotes
-                    let c = it.position(|(_, _ch)| _ch == ch).ok_or("cannot find endin quote")?;
-                    let (start, end) = (i + 1, i + c + 1);
-                    Some(Token::QuotedString((&self[start..end]).into()))
+                    // extract string literal including the quotes
+                    let end = i + quoted_len(&self[i..])
+                        .ok_or_else(|| ParseError::Incomplete("cannot find endin quote".to_string()))?;
+                    while it.peek().is_some_and(|&(j, _)| j < end) {
+                        it.next();
+                    }
+                    Some(Token::QuotedString((&self[i..end]).into()))
+                }
+                '$' if self[i..].starts_with("${") => {
+                    let end = i + self[i..]
+                        .find('}')
+                        .ok_or_else(|| ParseError::Incomplete("cannot find closing brace".to_string()))?
+                        + 1;
+                    while it.peek().is_some_and(|&(j, _)| j < end) {
+                        it.next();
+                    }
+                    Some(Token::VarString((&self[i..end]).into()))
+                }
+                // array assignment `name=(a b c)` stays a single word
+                '(' if capture_state && self[start..i].ends_with('=') => {
+                    let end = i + parenthesized_len(&self[i..])
+                        .ok_or_else(|| ParseError::Incomplete("cannot find closing parenthesis".to_string()))?;
+                    while it.peek().is_some_and(|&(j, _)| j < end) {
+                        it.next();
+                    }
+                    None
                 }
                 _ => {
                     let remaining_str = &self[i..];
                     if let Some(s) = try_extract_symbol_at_start(remaining_str) {
+                        // skip the rest of multi-character symbols like `&&`
+                        for _ in 1..s.len() {
+                            it.next();
+                        }
                         Some(Token::Symbol(s))
                     } else {
                         if !capture_state {
@@ -150,10 +302,12 @@ fn test_tokenizer() {
     assert!(it.next() == Some(&Token::VarString(Cow::from("echo"))));
     assert!(it.next() == Some(&Token::VarString(Cow::from("void"))));
     assert!(it.next() == Some(&Token::Symbol("&")));
-    assert!(it.next() == Some(&Token::VarString(Cow::from("sleep"))));
+    assert!(it.next() == Some(&Token::VarString(Cow::from("'sle''ep'"))));
     assert!(it.next() == Some(&Token::VarString(Cow::from("1000h"))));
     assert!(it.next() == Some(&Token::Symbol(";")));
     assert!(it.next() == Some(&Token::VarString(Cow::from("echo"))));
+    assert!(it.next() == Some(&Token::VarString(Cow::from("'%^;'"))));
+    assert!(it.next().is_none());
 }
 
 #[test]
@@ -161,5 +315,75 @@ fn test_symbol_presence() {
     let string = "<< Help";
     println!("starts with {:?}", try_extract_symbol_at_start(string));
     assert!(try_extract_symbol_at_start(string) == Some("<<"));
-    assert!(try_extract_symbol_at_start(&string[2..]) == None);
+    assert!(try_extract_symbol_at_start(&string[2..]).is_none());
+}
+
+#[test]
+fn test_array_assignment_word() {
+    use std::borrow::Cow;
+
+    let tokens = "arr=(a 'b )' \\) c) echo".tokenize().unwrap();
+    let mut it = tokens.get_stream();
+    assert!(it.next() == Some(&Token::VarString(Cow::from("arr=(a 'b )' \\) c)"))));
+    assert!(it.next() == Some(&Token::VarString(Cow::from("echo"))));
+}
+
+#[test]
+fn test_multi_character_symbols() {
+    let tokens = "a && b||c >> d".tokenize().unwrap();
+    let symbols: Vec<_> = tokens.get_stream().filter_map(|tok| tok.symbol()).collect();
+    assert_eq!(symbols, vec!["&&", "||", ">>"]);
+}
+
+#[test]
+fn test_incomplete_input() {
+    assert!(matches!("echo 'a\n".tokenize(), Err(ParseError::Incomplete(_))));
+    assert!(matches!("echo \"a\\\"\n".tokenize(), Err(ParseError::Incomplete(_))));
+    assert!(matches!("echo a \\\n".tokenize(), Err(ParseError::Incomplete(_))));
+    assert!(matches!("arr=(a\n".tokenize(), Err(ParseError::Incomplete(_))));
+}
+
+#[test]
+fn test_comments_and_continuations() {
+    use std::borrow::Cow;
+
+    let tokens = "# comment\nec\\\nho a#b '#'#c \\\n #d; e\n".tokenize().unwrap();
+    let mut it = tokens.get_stream();
+    assert!(it.next() == Some(&Token::VarString(Cow::from("echo"))));
+    assert!(it.next() == Some(&Token::VarString(Cow::from("a#b"))));
+    assert!(it.next() == Some(&Token::VarString(Cow::from("'#'#c"))));
+    assert!(it.next() == Some(&Token::Symbol(";")));
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn test_newline_separator() {
+    let tokens = "\na\n\nb &\nc &&\nd |\ne\n".tokenize().unwrap();
+    let symbols: Vec<_> = tokens.get_stream().filter_map(|tok| tok.symbol()).collect();
+    assert_eq!(symbols, vec![";", "&", "&&", "|", ";"]);
+}
+
+#[test]
+fn test_expand_aliases() {
+    aliases::set("test_ll", "ls -l");
+    aliases::set("test_sudo", "sudo ");
+    aliases::set("test_ls", "test_ls -a;");
+
+    let words = |line: &str| -> Vec<String> {
+        let tokens = line.tokenize().unwrap().expand_aliases();
+        let words = tokens.get_stream().map(|tok| match tok {
+            Token::Symbol(symbol) => symbol.to_string(),
+            Token::VarString(word) | Token::QuotedString(word) => word.to_string(),
+            Token::WhiteSpace => " ".to_string(),
+        });
+        words.collect()
+    };
+    assert_eq!(words("x=1 test_ll test_ll\n"), vec!["x=1", "ls", "-l", "test_ll", ";"]);
+    assert_eq!(words("test_sudo test_ll > test_ll"), vec!["sudo", "ls", "-l", ">", "test_ll"]);
+    assert_eq!(words("test_ls test_ls\n"), vec!["test_ls", "-a", ";", "test_ls", "-a", ";"]);
+    assert_eq!(words("'test_ll' | test_ll"), vec!["'test_ll'", "|", "ls", "-l"]);
+
+    for name in &["test_ll", "test_sudo", "test_ls"] {
+        aliases::unset(name);
+    }
 }
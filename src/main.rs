@@ -1,59 +1,163 @@
-#![feature(nll)]
-#![feature(box_syntax, box_patterns)]
+#![feature(box_patterns)]
 #![feature(associated_type_defaults)]
 #![feature(iter_advance_by)]
 #![warn(rust_2018_idioms)]
 
+use nix::libc;
+use nix::unistd;
 use std::env;
-use std::io;
-use std::io::Write;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process;
 
+mod aliases;
+mod arith;
 mod builtin;
+mod commands;
+mod dirstack;
+mod expand;
 mod interpret;
+mod jobs;
 mod lexer;
+mod options;
 mod parser;
+mod quote;
+mod traps;
+mod variables;
 
-use crate::{lexer::Tokenizer, parser::Parse};
+use crate::interpret::Reading;
 
+/// Where the shell reads its commands from
+enum Source {
+    Stdin,
+    /// The string given with `-c`
+    Command(String),
+    /// Path of a script file
+    Script(String),
+}
+
+/// Standard input read straight from the descriptor. Read a byte at a time, a script
+/// on it leaves the lines after the current command for the commands to read.
+struct RawStdin;
+
+impl Read for RawStdin {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match unsafe { libc::read(0, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) } {
+            -1 => Err(io::Error::last_os_error()),
+            read => Ok(read as usize),
+        }
+    }
+}
+
+/// Reads and runs commands until the shell exits, prompting for them when interactive
+fn run(reader: &mut dyn BufRead, interactive: bool) -> ! {
+    let reading = if interactive { Reading::Interactive } else { Reading::Script };
+    // only sourced commands return from the reader loop
+    interpret::interpret_script(reader, reading).ok();
+    unreachable!()
+}
+
+/// Runs `~/.myshrc` when interactive, then the file named by `ENV`. Missing files are skipped.
+fn load_startup_files(interactive: bool) {
+    let rc = variables::get("HOME")
+        .filter(|_| interactive)
+        .map(|home| PathBuf::from(home).join(".myshrc"));
+    // the value of `ENV` undergoes parameter expansion
+    let env = variables::get("ENV")
+        .and_then(|value| expand::expand_string(&value).ok())
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from);
+
+    for path in rc.into_iter().chain(env).filter(|path| path.is_file()) {
+        if let Err(e) = builtin::source::source_file(&path) {
+            eprintln!("mysh: {}: {}", path.display(), e);
+        }
+    }
+}
+
+// mysh [--norc] [-is] [-efuvx] [-c command [name [args...]] | script [args...]]
 fn main() {
-    builtin::set_shell_signal_handlers();
-
-    loop {
-        print!("{}", builtin::get_prompt());
-        io::stdout().flush().expect("Failed to flush");
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
-
-        // end of stream
-        if input.is_empty() {
-            return;
-        }
-
-        let debug_print = env::var("DEBUG_PRINT").is_ok();
-
-        // put it to lexer
-        match input.as_str().tokenize() {
-            Ok(tokens) => {
-                if debug_print {
-                    println!("Tokens: {:?}", &tokens);
-                }
-                let parsed_result = tokens.get_stream().parse();
-                match parsed_result {
-                    Ok(Some(expr)) => {
-                        if debug_print {
-                            println!("Syntax Tree: \n{:#?}\n", &expr);
-                        }
-                        if let Err(e) = interpret::interpret(&*expr) {
-                            println!("Error executing: {}", e);
-                        }
-                    }
+    let args: Vec<String> = env::args().collect();
 
-                    Ok(None) => (),
-                    Err(e) => println!("Error in parsing: {}", e),
-                };
+    let (mut command_mode, mut stdin_mode, mut interactive) = (false, false, false);
+    let mut norc = false;
+    let mut operands = &args[1..];
+    while let Some(arg) = operands.first() {
+        if arg == "--norc" {
+            norc = true;
+            operands = &operands[1..];
+            continue;
+        }
+        if arg == "--" || arg == "-" {
+            operands = &operands[1..];
+            break;
+        }
+        let enabled = arg.starts_with('-');
+        if arg.len() < 2 || !(enabled || arg.starts_with('+')) {
+            break;
+        }
+        for letter in arg[1..].chars() {
+            match letter {
+                'c' => command_mode = true,
+                's' => stdin_mode = true,
+                'i' => interactive = true,
+                _ => match options::by_letter(letter) {
+                    Some(name) => options::set(name, enabled).unwrap(),
+                    None => {
+                        eprintln!("mysh: {}{}: invalid option", &arg[..1], letter);
+                        process::exit(2);
+                    }
+                },
             }
-            Err(e) => println!("Error in lexing: {}", e),
         }
+        operands = &operands[1..];
+    }
+
+    let source = match operands.split_first() {
+        Some((command, rest)) if command_mode => {
+            // the operand after the command string becomes `$0`
+            variables::set_arg0(rest.first().unwrap_or(&args[0]));
+            operands = rest.get(1..).unwrap_or(&[]);
+            Source::Command(command.clone())
+        }
+        None if command_mode => {
+            eprintln!("mysh: -c: option requires an argument");
+            process::exit(2);
+        }
+        Some((script, rest)) if !stdin_mode => {
+            variables::set_arg0(script);
+            operands = rest;
+            Source::Script(script.clone())
+        }
+        _ => {
+            variables::set_arg0(&args[0]);
+            Source::Stdin
+        }
+    };
+    variables::set_positional(operands.to_vec());
+
+    interactive |= matches!(source, Source::Stdin) && unistd::isatty(0).unwrap_or(false);
+    if interactive {
+        jobs::init();
+    }
+    traps::set_shell_signal_handlers();
+    variables::import_environment();
+    builtin::cd::init_pwd();
+    if !norc {
+        load_startup_files(interactive);
+    }
+
+    match source {
+        Source::Stdin if interactive => run(&mut io::stdin().lock(), interactive),
+        Source::Stdin => run(&mut BufReader::with_capacity(1, RawStdin), interactive),
+        Source::Command(command) => run(&mut command.as_bytes(), interactive),
+        Source::Script(path) => match File::open(&path) {
+            Ok(file) => run(&mut BufReader::new(file), interactive),
+            Err(e) => {
+                eprintln!("mysh: {}: {}", path, e);
+                process::exit(if e.kind() == io::ErrorKind::NotFound { 127 } else { 126 });
+            }
+        },
     }
 }
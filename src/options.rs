@@ -0,0 +1,72 @@
+//! Options module holds the shell options changed with `set -o` and `set +o`
+
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Option names with their single-letter flag, in the order `set -o` lists them
+const OPTIONS: &[(&str, Option<char>)] = &[
+    ("errexit", Some('e')),
+    // accepted for scripts that set it, there being no pathname expansion to turn off
+    ("noglob", Some('f')),
+    ("nounset", Some('u')),
+    ("pipefail", None),
+    ("verbose", Some('v')),
+    ("xtrace", Some('x')),
+];
+
+lazy_static! {
+    static ref ENABLED: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+}
+
+pub fn is_set(name: &str) -> bool {
+    ENABLED.lock().unwrap().contains(name)
+}
+
+pub fn set(name: &str, enabled: bool) -> Result<(), String> {
+    let name = OPTIONS
+        .iter()
+        .map(|&(option, _)| option)
+        .find(|&option| option == name)
+        .ok_or_else(|| format!("{}: invalid option name", name))?;
+    let mut options = ENABLED.lock().unwrap();
+    if enabled {
+        options.insert(name);
+    } else {
+        options.remove(name);
+    }
+    Ok(())
+}
+
+/// Every option along with whether it is enabled
+pub fn all() -> Vec<(&'static str, bool)> {
+    let options = ENABLED.lock().unwrap();
+    OPTIONS.iter().map(|&(name, _)| (name, options.contains(name))).collect()
+}
+
+/// Name of the option a `set` flag letter stands for
+pub fn by_letter(letter: char) -> Option<&'static str> {
+    OPTIONS.iter().find(|&&(_, flag)| flag == Some(letter)).map(|&(name, _)| name)
+}
+
+/// Letters of the enabled options, the value of `$-`
+pub fn flags() -> String {
+    let options = ENABLED.lock().unwrap();
+    OPTIONS
+        .iter()
+        .filter(|(name, _)| options.contains(name))
+        .filter_map(|&(_, flag)| flag)
+        .collect()
+}
+
+#[test]
+fn test_options() {
+    assert!(!is_set("pipefail"));
+    assert_eq!(set("pipefail", true), Ok(()));
+    assert!(all().contains(&("pipefail", true)));
+    assert_eq!(set("pipefail", false), Ok(()));
+    assert!(!is_set("pipefail"));
+    assert_eq!(set("nosuch", true), Err("nosuch: invalid option name".to_string()));
+    assert_eq!(by_letter('f'), Some("noglob"));
+    assert_eq!(by_letter('o'), None);
+}
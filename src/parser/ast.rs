@@ -1,10 +1,12 @@
-/**
+/*
  *
     <command line>	::=  	<job>
                         |	<job> '&'
                         | 	<job> '&' <command line>
                         |	<job> ';'
                         |	<job> ';' <command line>
+                        |	<job> '&&' <command line>
+                        |	<job> '||' <command line>
 
     <job>			::=		<command>
                         |	< job > '|' < command >
@@ -15,6 +17,8 @@
 
     <simple command>::=		<pathname>
                         |	<simple command>  <token>
+                        |	<assignment>
+                        |	<assignment> <simple command>
  *
  *
  *
@@ -25,6 +29,8 @@ use std::fmt;
 pub enum CommandLineOp {
     Background,
     Sequence,
+    And,
+    Or,
 }
 
 #[derive(Debug, PartialEq)]
@@ -61,6 +67,7 @@ pub enum CommandExpr {
 pub enum SimpleCmdExpr {
     Exe(String),
     ExeWithArg(String, Vec<String>),
+    Assign(Vec<String>, Option<Box<SimpleCmdExpr>>), // `name=value` words before the command
 }
 
 impl fmt::Debug for SimpleCmdExpr {
@@ -68,6 +75,39 @@ impl fmt::Debug for SimpleCmdExpr {
         match &self {
             SimpleCmdExpr::Exe(exepath) => write!(f, "{:?}", exepath),
             SimpleCmdExpr::ExeWithArg(exepath, args) => write!(f, "{:?} {:?}", exepath, args),
+            SimpleCmdExpr::Assign(assignments, None) => write!(f, "{:?}", assignments),
+            SimpleCmdExpr::Assign(assignments, Some(cmd)) => write!(f, "{:?} {:?}", assignments, cmd),
+        }
+    }
+}
+
+// source-like rendering of a job, used when listing jobs
+impl fmt::Display for JobExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobExpr::Type1(cmd) => write!(f, "{}", cmd),
+            JobExpr::Type2(cmd, JobOp::Pipe, job) => write!(f, "{} | {}", cmd, job),
+        }
+    }
+}
+
+impl fmt::Display for CommandExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandExpr::Type1(cmd) => write!(f, "{}", cmd),
+            CommandExpr::Type2(cmd, CommandOp::RedirectIn, filename) => write!(f, "{} < {}", cmd, filename),
+            CommandExpr::Type2(cmd, CommandOp::RedirectOut, filename) => write!(f, "{} > {}", cmd, filename),
+        }
+    }
+}
+
+impl fmt::Display for SimpleCmdExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimpleCmdExpr::Exe(exepath) => write!(f, "{}", exepath),
+            SimpleCmdExpr::ExeWithArg(exepath, args) => write!(f, "{} {}", exepath, args.join(" ")),
+            SimpleCmdExpr::Assign(assignments, None) => write!(f, "{}", assignments.join(" ")),
+            SimpleCmdExpr::Assign(assignments, Some(cmd)) => write!(f, "{} {}", assignments.join(" "), cmd),
         }
     }
 }
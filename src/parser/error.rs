@@ -0,0 +1,19 @@
+//! Error module containing the errors raised while lexing and parsing
+
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    /// Input ended in the middle of a construct, more input may complete it
+    Incomplete(String),
+    /// Input that no amount of further input can make valid
+    Invalid(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Incomplete(msg) | ParseError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
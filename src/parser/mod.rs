@@ -4,5 +4,9 @@ pub use self::token::*;
 mod ast;
 pub use self::ast::*;
 
+mod error;
+pub use self::error::*;
+
+#[allow(clippy::module_inception)]
 mod parser;
-pub use self::parser::*;
\ No newline at end of file
+pub use self::parser::*;
@@ -11,38 +11,33 @@ pub trait TokenStream<'a>:
 
 pub trait Parse: Sized
 {
-    type ParserError = String;
+    type ParserError = ParseError;
     fn parse(self) -> Result<Option<Box<CommandLineExpr>>, Self::ParserError>;
 }
 
 struct ParserData<T>
 {
-    token_iterator: T
+    token_iterator: T,
+    // ran out of tokens where more were expected, i.e after a trailing `|`
+    incomplete: bool,
 }
 
 impl<'a, I> ParserData<I> where I: TokenStream<'a>
 {
     fn new(toker_iter: I) -> Self {
-        ParserData { token_iterator: toker_iter }
+        ParserData { token_iterator: toker_iter, incomplete: false }
     }
 
     fn create_commandline_expr(&mut self) -> Option<Box<CommandLineExpr>> {
         let job_expr = self.create_job_expr()?;
 
         let mut cloned_iter = self.token_iterator.clone();
-        let tok = cloned_iter.next();
-        if tok.is_none()
-            || !tok.unwrap().is_symbol()
-            || !(tok.unwrap().symbol().unwrap() == "&" || tok.unwrap().symbol().unwrap() == ";")
-        {
-            return Some(Box::new(CommandLineExpr::Type1(job_expr)));
-        }
-
-        let symbol = tok.unwrap().symbol().unwrap();
-        let cmd_line_op = if symbol == ";" {
-            CommandLineOp::Sequence
-        } else {
-            CommandLineOp::Background
+        let cmd_line_op = match cloned_iter.next().and_then(|tok| tok.symbol()) {
+            Some(";") => CommandLineOp::Sequence,
+            Some("&") => CommandLineOp::Background,
+            Some("&&") => CommandLineOp::And,
+            Some("||") => CommandLineOp::Or,
+            _ => return Some(Box::new(CommandLineExpr::Type1(job_expr))),
         };
 
         self.token_iterator.next();
@@ -50,6 +45,10 @@ impl<'a, I> ParserData<I> where I: TokenStream<'a>
         let next_cmdline_expr = self.create_commandline_expr();
         if next_cmdline_expr.is_none() {
             self.token_iterator = cloned_iter;
+            // `&&` and `||` can't end a command line
+            if cmd_line_op == CommandLineOp::And || cmd_line_op == CommandLineOp::Or {
+                self.incomplete |= self.token_iterator.clone().next().is_none();
+            }
             return Some(Box::new(CommandLineExpr::Type2(job_expr, cmd_line_op)));
         }
 
@@ -73,6 +72,7 @@ impl<'a, I> ParserData<I> where I: TokenStream<'a>
         let next_job_expr = self.create_job_expr();
         if next_job_expr.is_none() {
             self.token_iterator = cloned_iter.clone();
+            self.incomplete |= self.token_iterator.clone().next().is_none();
             return None;
         }
 
@@ -115,6 +115,24 @@ impl<'a, I> ParserData<I> where I: TokenStream<'a>
     }
 
     fn create_simplecmd_expr(&mut self) -> Option<Box<SimpleCmdExpr>> {
+        let mut assignments = vec![];
+        while let Some(tok) = self.token_iterator.clone().next() {
+            if !tok.is_assignment() {
+                break;
+            }
+            assignments.push(tok.varstring().unwrap());
+            self.token_iterator.next();
+        }
+
+        let exe_expr = self.create_exe_expr();
+        if assignments.is_empty() {
+            exe_expr
+        } else {
+            Some(Box::new(SimpleCmdExpr::Assign(assignments, exe_expr)))
+        }
+    }
+
+    fn create_exe_expr(&mut self) -> Option<Box<SimpleCmdExpr>> {
         let mut cloned_iter = self.token_iterator.clone().peekable();
 
         let tok = cloned_iter.next()?;
@@ -142,10 +160,13 @@ impl<'a, I> ParserData<I> where I: TokenStream<'a>
         }
     }
 
-    fn parse(&mut self) -> Result<Option<Box<CommandLineExpr>>, String> {
+    fn parse(&mut self) -> Result<Option<Box<CommandLineExpr>>, ParseError> {
         let syntree = self.create_commandline_expr();
-        if let Some(remaining_tok) = self.token_iterator.next() {
-            Err(format!("Unexpected token: {:?}", remaining_tok))
+        if self.incomplete {
+            Err(ParseError::Incomplete("unexpected end of input".to_string()))
+        }
+        else if let Some(remaining_tok) = self.token_iterator.next() {
+            Err(ParseError::Invalid(format!("Unexpected token: {:?}", remaining_tok)))
         }
         else {
             Ok(syntree)
@@ -154,7 +175,7 @@ impl<'a, I> ParserData<I> where I: TokenStream<'a>
 }
 
 impl<'a, T: TokenStream<'a> + Clone> Parse for T {
-    type ParserError = String;
+    type ParserError = ParseError;
 
     fn parse(self) -> Result<Option<Box<CommandLineExpr>>, Self::ParserError> {
         let mut data = ParserData::new(self);
@@ -186,3 +207,91 @@ fn test_cmdline_expr() {
         )
     );
 }
+
+#[test]
+fn test_assignment_expr() {
+    use crate::lexer::*;
+    use matches::assert_matches;
+    let input = "a=1 b='2 3' env a=4; c=5";
+    let tokens = input.tokenize().unwrap();
+    let mut parser = ParserData::new(tokens.get_stream());
+    assert_matches!(
+        parser.parse().unwrap().unwrap(),
+        box CommandLineExpr::Type3(
+            box JobExpr::Type1(box CommandExpr::Type1(
+                box SimpleCmdExpr::Assign(ref assignments, Some(box SimpleCmdExpr::ExeWithArg(_, ref args))),
+            )),
+            CommandLineOp::Sequence,
+            box CommandLineExpr::Type1(box JobExpr::Type1(box CommandExpr::Type1(
+                box SimpleCmdExpr::Assign(_, None),
+            ))),
+        ) if assignments.len() == 2 && args == &["a=4".to_string()]
+    );
+}
+
+#[test]
+fn test_and_or_expr() {
+    use crate::lexer::*;
+    use matches::assert_matches;
+    let input = "true && false || echo";
+    let tokens = input.tokenize().unwrap();
+    let mut parser = ParserData::new(tokens.get_stream());
+    assert_matches!(
+        parser.parse().unwrap().unwrap(),
+        box CommandLineExpr::Type3(
+            _,
+            CommandLineOp::And,
+            box CommandLineExpr::Type3(_, CommandLineOp::Or, box CommandLineExpr::Type1(_)),
+        )
+    );
+}
+
+#[test]
+fn test_and_or_precedence() {
+    use crate::lexer::*;
+    use matches::assert_matches;
+    // operators joining the jobs of a command line, in order
+    fn ops(expr: &CommandLineExpr) -> Vec<&CommandLineOp> {
+        match expr {
+            CommandLineExpr::Type1(_) => vec![],
+            CommandLineExpr::Type2(_, op) => vec![op],
+            CommandLineExpr::Type3(_, op, next) => std::iter::once(op).chain(ops(next)).collect(),
+        }
+    }
+    let parse = |input: &str| input.tokenize().unwrap().get_stream().parse().unwrap().unwrap();
+
+    // `&&` and `||` have equal precedence, chained in the order they appear to run left to right
+    let expr = parse("false || true && echo");
+    assert_eq!(ops(&expr), [&CommandLineOp::Or, &CommandLineOp::And]);
+
+    // `;` and `&` bind looser, ending the and-or list before them
+    let expr = parse("false && a; b || c && d & e");
+    let expected = [
+        &CommandLineOp::And,
+        &CommandLineOp::Sequence,
+        &CommandLineOp::Or,
+        &CommandLineOp::And,
+        &CommandLineOp::Background,
+    ];
+    assert_eq!(ops(&expr), expected);
+
+    // `|` binds tighter than `&&`, a pipeline being one job of the list
+    assert_matches!(
+        parse("a | b && c"),
+        box CommandLineExpr::Type3(box JobExpr::Type2(..), CommandLineOp::And, box CommandLineExpr::Type1(_))
+    );
+}
+
+#[test]
+fn test_incomplete_expr() {
+    use crate::lexer::*;
+    use matches::assert_matches;
+    let parse = |input: &str| input.tokenize().unwrap().get_stream().parse();
+
+    assert_matches!(parse("ls |\n"), Err(ParseError::Incomplete(_)));
+    assert_matches!(parse("true &&"), Err(ParseError::Incomplete(_)));
+    assert_matches!(parse("false || true ||"), Err(ParseError::Incomplete(_)));
+    assert_matches!(parse("ls | ;"), Err(ParseError::Invalid(_)));
+    assert_matches!(parse("&& ls"), Err(ParseError::Invalid(_)));
+    assert_matches!(parse("sleep 1 &"), Ok(Some(_)));
+}
@@ -1,21 +1,17 @@
 //! Token module containing token definitions
 
+use crate::variables::is_valid_name;
 use std::borrow::Cow;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Default)]
 pub enum Token<'a> {
+    #[default]
     WhiteSpace,
     Symbol(&'static str), // i.e ';', '&', etc
     QuotedString(Cow<'a, str>),
     VarString(Cow<'a, str>), // string slice representing commands, parameters to commands, etc
 }
 
-impl<'a> Default for Token<'a> {
-    fn default() -> Token<'a> {
-        Token::WhiteSpace
-    }
-}
-
 impl<'a> Token<'a> {
     pub fn varstring(&self) -> Option<String> {
         match self {
@@ -32,18 +28,27 @@ impl<'a> Token<'a> {
     }
 
     pub fn is_varstring(&self) -> bool {
-        if let Token::VarString(_) = self {
-            true
-        } else {
-            false
-        }
+        matches!(self, Token::VarString(_))
     }
 
     pub fn is_symbol(&self) -> bool {
-        if let Token::Symbol(_) = self {
-            true
-        } else {
-            false
+        matches!(self, Token::Symbol(_))
+    }
+
+    /// `name=value` or `name[subscript]=value` words, which are assignments
+    /// when they precede the command
+    pub fn is_assignment(&self) -> bool {
+        let lhs = match self {
+            Token::VarString(s) => match s.find('=') {
+                Some(n) => &s[..n],
+                None => return false,
+            },
+            _ => return false,
+        };
+
+        match lhs.find('[') {
+            Some(n) => lhs.ends_with(']') && is_valid_name(&lhs[..n]),
+            None => is_valid_name(lhs),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,21 @@
+//! Quote module quotes strings so the shell reads them back as the same single word,
+//! as `set -x`, `alias`, `trap -p` and `declare -p` print them
+
+/// Single-quotes the string, leaving words made of plain characters as they are
+pub fn quote(word: &str) -> String {
+    let is_plain = |ch: char| ch.is_ascii_alphanumeric() || "-_./=:,+@%^".contains(ch);
+    if !word.is_empty() && word.chars().all(is_plain) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote("ls"), "ls");
+    assert_eq!(quote("/usr/bin:/bin"), "/usr/bin:/bin");
+    assert_eq!(quote(""), "''");
+    assert_eq!(quote("a b"), "'a b'");
+    assert_eq!(quote("~/$HOME's"), "'~/$HOME'\\''s'");
+}
@@ -0,0 +1,282 @@
+//! Traps module holds the commands registered with `trap` and the shell's signal dispositions.
+//!
+//! Caught signals are only recorded by the handler, their commands run later
+//! from the interpreter at safe points, between jobs and before the prompt.
+
+use crate::interpret;
+use crate::jobs;
+use lazy_static::lazy_static;
+use nix::libc::c_int;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Signals the shell itself ignores while interactive
+const SHELL_IGNORED: &[Signal] = &[
+    Signal::SIGINT,
+    Signal::SIGQUIT,
+    Signal::SIGTSTP,
+    Signal::SIGTTIN,
+    Signal::SIGTTOU,
+];
+
+/// Signals that end the shell, caught while an `EXIT` trap is set so that it runs first
+const FATAL: &[Signal] = &[Signal::SIGHUP, Signal::SIGINT, Signal::SIGTERM];
+
+/// Bit `n - 1` is set for signal `n` once it is caught, until its trap has run
+static PENDING: AtomicU64 = AtomicU64::new(0);
+
+/// Signals that were already ignored when the shell started, bit `n - 1` for signal `n`
+static IGNORED_ON_ENTRY: AtomicU64 = AtomicU64::new(0);
+
+/// Set while a trap command runs, so traps don't trigger one another
+static IN_TRAP: AtomicBool = AtomicBool::new(false);
+
+/// Set once the `EXIT` trap has started, which runs a single time
+static EXITING: AtomicBool = AtomicBool::new(false);
+
+/// What a trap is set on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    /// The shell exiting
+    Exit,
+    Signal(Signal),
+    /// A job failing
+    Err,
+    /// Each job about to run
+    Debug,
+    /// A sourced script finishing
+    Return,
+}
+
+impl Condition {
+    /// Name as `trap -p` prints it
+    pub fn name(self) -> String {
+        match self {
+            Condition::Exit => "EXIT".to_string(),
+            Condition::Signal(signal) => signal.as_ref().to_string(),
+            Condition::Err => "ERR".to_string(),
+            Condition::Debug => "DEBUG".to_string(),
+            Condition::Return => "RETURN".to_string(),
+        }
+    }
+
+    // listing order, the way signal numbers go with `EXIT` being 0
+    fn order(self) -> i32 {
+        match self {
+            Condition::Exit => 0,
+            Condition::Signal(signal) => signal as i32,
+            Condition::Debug => 100,
+            Condition::Err => 101,
+            Condition::Return => 102,
+        }
+    }
+}
+
+lazy_static! {
+    /// Trap commands, an empty command ignoring the signal
+    static ref TRAPS: Mutex<Vec<(Condition, String)>> = Mutex::new(vec![]);
+}
+
+fn bit(signal: Signal) -> u64 {
+    1 << (signal as i32 - 1)
+}
+
+extern "C" fn record_signal(signum: c_int) {
+    PENDING.fetch_or(1 << (signum - 1), Ordering::SeqCst);
+}
+
+fn set_handler(signal: Signal, handler: SigHandler) -> nix::Result<SigAction> {
+    // restarting system calls keeps `waitpid` and reads going while a trapped signal arrives
+    let action = SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty());
+    unsafe { sigaction(signal, &action) }
+}
+
+fn has_exit_trap() -> bool {
+    get(Condition::Exit).is_some_and(|command| !command.is_empty())
+}
+
+// disposition the shell uses for a signal without a trap
+fn shell_default(signal: Signal) -> SigHandler {
+    let ignored_on_entry = FATAL.contains(&signal) && IGNORED_ON_ENTRY.load(Ordering::SeqCst) & bit(signal) != 0;
+    if ignored_on_entry || jobs::is_enabled() && SHELL_IGNORED.contains(&signal) {
+        SigHandler::SigIgn
+    } else if FATAL.contains(&signal) && has_exit_trap() {
+        SigHandler::Handler(record_signal)
+    } else {
+        SigHandler::SigDfl
+    }
+}
+
+/// Sets the shell's own signal dispositions, ignoring job control and
+/// keyboard signals when interactive
+pub fn set_shell_signal_handlers() {
+    for &signal in FATAL {
+        let current = set_handler(signal, SigHandler::SigIgn).unwrap();
+        if current.handler() == SigHandler::SigIgn {
+            IGNORED_ON_ENTRY.fetch_or(bit(signal), Ordering::SeqCst);
+        }
+        set_handler(signal, current.handler()).unwrap();
+    }
+    for &signal in SHELL_IGNORED {
+        let previous = set_handler(signal, shell_default(signal)).unwrap();
+        if previous.handler() == SigHandler::SigIgn {
+            IGNORED_ON_ENTRY.fetch_or(bit(signal), Ordering::SeqCst);
+        }
+    }
+}
+
+/// Gives a child the dispositions it should start with: signals trapped with an
+/// empty command or ignored when the shell started stay ignored, the rest are reset
+pub fn disable_shell_signal_handlers() {
+    let ignored_on_entry = IGNORED_ON_ENTRY.load(Ordering::SeqCst);
+    let traps = TRAPS.lock().unwrap();
+    let trapped = traps.iter().filter_map(|(condition, command)| match condition {
+        Condition::Signal(signal) => Some((*signal, command.is_empty())),
+        _ => None,
+    });
+    let shell_handled = SHELL_IGNORED.iter().chain(FATAL).map(|&signal| (signal, false));
+
+    for (signal, ignored) in shell_handled.chain(trapped) {
+        let ignored = ignored || ignored_on_entry & bit(signal) != 0;
+        let handler = if ignored { SigHandler::SigIgn } else { SigHandler::SigDfl };
+        set_handler(signal, handler).ok();
+    }
+}
+
+// whether a trap carries over to a subshell, which only signals ignored with an empty command do
+fn kept_in_subshell(&(condition, ref command): &(Condition, String)) -> bool {
+    matches!(condition, Condition::Signal(_)) && command.is_empty()
+}
+
+/// Resets the traps in a forked copy of the shell, whose commands belong to the
+/// parent shell. Ignored signals stay ignored, the other traps are cleared.
+pub fn enter_subshell() {
+    disable_shell_signal_handlers();
+    PENDING.store(0, Ordering::SeqCst);
+    TRAPS.lock().unwrap().retain(kept_in_subshell);
+}
+
+/// Command set on the condition, if any
+pub fn get(condition: Condition) -> Option<String> {
+    let traps = TRAPS.lock().unwrap();
+    traps.iter().find(|(c, _)| *c == condition).map(|(_, command)| command.clone())
+}
+
+/// Sets the command run on the condition, an empty one ignoring a signal.
+/// `None` resets the condition to what the shell does without a trap.
+pub fn set(condition: Condition, command: Option<String>) -> Result<(), String> {
+    if let Condition::Signal(signal) = condition {
+        let handler = match &command {
+            None => shell_default(signal),
+            Some(command) if command.is_empty() => SigHandler::SigIgn,
+            Some(_) => SigHandler::Handler(record_signal),
+        };
+        set_handler(signal, handler).map_err(|e| format!("{}: {}", condition.name(), e))?;
+    }
+
+    let mut traps = TRAPS.lock().unwrap();
+    traps.retain(|(c, _)| *c != condition);
+    if let Some(command) = command {
+        traps.push((condition, command));
+    }
+    drop(traps);
+
+    // signals ending the shell are caught while there is an `EXIT` trap to run
+    if condition == Condition::Exit {
+        for &signal in FATAL.iter().filter(|&&signal| get(Condition::Signal(signal)).is_none()) {
+            set_handler(signal, shell_default(signal)).ok();
+        }
+    }
+    Ok(())
+}
+
+/// Every trap set, in the order `trap -p` lists them
+pub fn all() -> Vec<(Condition, String)> {
+    let mut traps = TRAPS.lock().unwrap().clone();
+    traps.sort_by_key(|(condition, _)| condition.order());
+    traps
+}
+
+/// Runs the command set on the condition. The exit status of the trap
+/// is discarded, `$?` keeps the status it had before. The `EXIT` trap runs
+/// once, even when another trap exits the shell.
+pub fn run(condition: Condition) {
+    let command = match get(condition) {
+        Some(command) if !command.is_empty() => command,
+        _ => return,
+    };
+    let was_in_trap = match condition {
+        Condition::Exit if EXITING.swap(true, Ordering::SeqCst) => return,
+        Condition::Exit => IN_TRAP.swap(true, Ordering::SeqCst),
+        _ if IN_TRAP.swap(true, Ordering::SeqCst) => return,
+        _ => false,
+    };
+
+    let status = interpret::last_status();
+    if let Err(e) = interpret::interpret_source(&command) {
+        eprintln!("mysh: trap: {}", e);
+    }
+    interpret::set_last_status(status);
+    IN_TRAP.store(was_in_trap, Ordering::SeqCst);
+}
+
+/// Runs the traps of the signals caught since the last call. A signal that
+/// ends the shell was only caught to run the `EXIT` trap, and exits.
+pub fn run_pending() {
+    if IN_TRAP.load(Ordering::SeqCst) {
+        return;
+    }
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    for signal in Signal::iterator() {
+        if pending & bit(signal) == 0 {
+            continue;
+        }
+        match get(Condition::Signal(signal)) {
+            Some(_) => run(Condition::Signal(signal)),
+            None if FATAL.contains(&signal) => interpret::exit_shell(128 + signal as i32),
+            None => (),
+        }
+    }
+}
+
+#[test]
+fn test_traps() {
+    assert_eq!(set(Condition::Return, Some("echo done".to_string())), Ok(()));
+    assert_eq!(get(Condition::Return), Some("echo done".to_string()));
+    assert_eq!(set(Condition::Signal(Signal::SIGUSR2), Some("".to_string())), Ok(()));
+    assert!(set(Condition::Signal(Signal::SIGKILL), Some("".to_string())).is_err());
+
+    let names: Vec<_> = all().into_iter().map(|(condition, _)| condition.name()).collect();
+    assert_eq!(names, vec!["SIGUSR2", "RETURN"]);
+
+    set(Condition::Return, None).unwrap();
+    set(Condition::Signal(Signal::SIGUSR2), None).unwrap();
+    assert_eq!(get(Condition::Return), None);
+
+    // a signal ending the shell is caught while there is an `EXIT` trap to run
+    assert_eq!(shell_default(Signal::SIGTERM), SigHandler::SigDfl);
+    set(Condition::Exit, Some("TEST_TRAPS_EXIT=ran".to_string())).unwrap();
+    assert_eq!(shell_default(Signal::SIGTERM), SigHandler::Handler(record_signal));
+
+    // and it runs when another trap exits the shell, though only once
+    IN_TRAP.store(true, Ordering::SeqCst);
+    run(Condition::Exit);
+    assert!(IN_TRAP.load(Ordering::SeqCst));
+    IN_TRAP.store(false, Ordering::SeqCst);
+    assert_eq!(crate::variables::get("TEST_TRAPS_EXIT"), Some("ran".to_string()));
+    crate::variables::unset("TEST_TRAPS_EXIT").unwrap();
+    run(Condition::Exit);
+    assert_eq!(crate::variables::get("TEST_TRAPS_EXIT"), None);
+
+    set(Condition::Exit, None).unwrap();
+    assert_eq!(shell_default(Signal::SIGTERM), SigHandler::SigDfl);
+}
+
+#[test]
+fn test_kept_in_subshell() {
+    assert!(kept_in_subshell(&(Condition::Signal(Signal::SIGINT), String::new())));
+    assert!(!kept_in_subshell(&(Condition::Signal(Signal::SIGINT), "echo int".to_string())));
+    assert!(!kept_in_subshell(&(Condition::Exit, "echo X".to_string())));
+    assert!(!kept_in_subshell(&(Condition::Err, String::new())));
+}
@@ -0,0 +1,400 @@
+//! Variables module holds the shell variable table.
+//!
+//! Shell variables live here rather than in the process environment; only
+//! variables carrying the export attribute are handed to child processes.
+
+use crate::arith;
+use lazy_static::lazy_static;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    Indexed(BTreeMap<usize, String>),
+    Associative(BTreeMap<String, String>),
+}
+
+impl Value {
+    /// The value referenced without a subscript, element 0 for arrays
+    pub fn scalar(&self) -> Option<&str> {
+        match self {
+            Value::Scalar(s) => Some(s),
+            Value::Indexed(array) => array.get(&0).map(String::as_str),
+            Value::Associative(array) => array.get("0").map(String::as_str),
+        }
+    }
+
+    pub fn elements(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(s) => vec![s.clone()],
+            Value::Indexed(array) => array.values().cloned().collect(),
+            Value::Associative(array) => array.values().cloned().collect(),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            Value::Scalar(_) => vec!["0".to_string()],
+            Value::Indexed(array) => array.keys().map(usize::to_string).collect(),
+            Value::Associative(array) => array.keys().cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Variable {
+    /// `None` when the variable has attributes but no value, i.e `export FOO`
+    pub value: Option<Value>,
+    pub exported: bool,
+    pub readonly: bool,
+    pub integer: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Assignment {
+    /// `name=value`
+    Scalar(String, String),
+    /// `name[subscript]=value`
+    Element(String, String, String),
+    /// `name=(value [subscript]=value ...)`
+    Array(String, Vec<(Option<String>, String)>),
+}
+
+impl Assignment {
+    pub fn name(&self) -> &str {
+        match self {
+            Assignment::Scalar(name, _)
+            | Assignment::Element(name, _, _)
+            | Assignment::Array(name, _) => name,
+        }
+    }
+}
+
+/// Stack of scopes, the first one being the global scope
+struct VariableTable {
+    scopes: Vec<HashMap<String, Variable>>,
+    /// `$0`, the name of the shell or of the script it runs
+    arg0: String,
+    /// `$1`, `$2`, ...
+    positional: Vec<String>,
+}
+
+lazy_static! {
+    static ref VARIABLES: Mutex<VariableTable> = Mutex::new(VariableTable {
+        scopes: vec![HashMap::new()],
+        arg0: "mysh".to_string(),
+        positional: vec![],
+    });
+}
+
+impl VariableTable {
+    fn find(&self, name: &str) -> Option<&Variable> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // the innermost existing definition, or a new one in the global scope
+    fn find_or_insert(&mut self, name: &str) -> &mut Variable {
+        let index = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name))
+            .unwrap_or(0);
+        self.scopes[index].entry(name.to_string()).or_default()
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {
+            chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Copies the process environment into the table as exported variables
+pub fn import_environment() {
+    let mut table = VARIABLES.lock().unwrap();
+    for (name, value) in env::vars() {
+        if is_valid_name(&name) {
+            let var = table.find_or_insert(&name);
+            var.value = Some(Value::Scalar(value));
+            var.exported = true;
+        }
+    }
+}
+
+pub fn lookup(name: &str) -> Option<Variable> {
+    VARIABLES.lock().unwrap().find(name).cloned()
+}
+
+pub fn get(name: &str) -> Option<String> {
+    lookup(name)
+        .and_then(|var| var.value)
+        .and_then(|value| value.scalar().map(String::from))
+}
+
+// indexed array subscripts are arithmetic, negative ones counting back from the end
+fn resolve_index(array: &BTreeMap<usize, String>, subscript: &str) -> Result<usize, String> {
+    let index = arith::evaluate(subscript)?;
+    let index = if index < 0 {
+        let end = array.keys().next_back().map_or(0, |last| *last as i64 + 1);
+        end + index
+    } else {
+        index
+    };
+
+    if index < 0 {
+        Err(format!("[{}]: bad array subscript", subscript))
+    } else {
+        Ok(index as usize)
+    }
+}
+
+pub fn get_element(name: &str, subscript: &str) -> Result<Option<String>, String> {
+    Ok(match lookup(name).and_then(|var| var.value) {
+        Some(Value::Associative(array)) => array.get(subscript).cloned(),
+        Some(Value::Indexed(array)) => array.get(&resolve_index(&array, subscript)?).cloned(),
+        Some(Value::Scalar(s)) => (arith::evaluate(subscript) == Ok(0)).then_some(s),
+        None => None,
+    })
+}
+
+// value the variable holds after the assignment, integer variables storing
+// the evaluated expression
+fn assigned_value(var: Variable, assignment: &Assignment) -> Result<Value, String> {
+    if var.readonly {
+        return Err(format!("{}: readonly variable", assignment.name()));
+    }
+
+    let integer = var.integer;
+    let convert = |value: &str| -> Result<String, String> {
+        if integer {
+            Ok(arith::evaluate(value)?.to_string())
+        } else {
+            Ok(value.to_string())
+        }
+    };
+
+    // a plain assignment to an array sets element 0
+    let (subscript, value) = match assignment {
+        Assignment::Scalar(_, value) => match var.value {
+            Some(Value::Indexed(_)) | Some(Value::Associative(_)) => ("0", value),
+            _ => return Ok(Value::Scalar(convert(value)?)),
+        },
+        Assignment::Element(_, subscript, value) => (&subscript[..], value),
+        Assignment::Array(name, elements) => {
+            return match var.value {
+                Some(Value::Associative(_)) => {
+                    let mut array = BTreeMap::new();
+                    for (key, value) in elements {
+                        let key = key.as_ref().ok_or_else(|| {
+                            format!("{}: {}: must use subscript when assigning associative array", name, value)
+                        })?;
+                        array.insert(key.clone(), convert(value)?);
+                    }
+                    Ok(Value::Associative(array))
+                }
+                _ => {
+                    let mut array = BTreeMap::new();
+                    let mut next = 0;
+                    for (key, value) in elements {
+                        if let Some(key) = key {
+                            next = resolve_index(&array, key)?;
+                        }
+                        array.insert(next, convert(value)?);
+                        next += 1;
+                    }
+                    Ok(Value::Indexed(array))
+                }
+            };
+        }
+    };
+
+    match var.value {
+        Some(Value::Associative(mut array)) => {
+            array.insert(subscript.to_string(), convert(value)?);
+            Ok(Value::Associative(array))
+        }
+        other => {
+            let mut array = match other {
+                Some(Value::Indexed(array)) => array,
+                Some(Value::Scalar(s)) => vec![(0, s)].into_iter().collect(),
+                _ => BTreeMap::new(),
+            };
+            let index = resolve_index(&array, subscript)?;
+            array.insert(index, convert(value)?);
+            Ok(Value::Indexed(array))
+        }
+    }
+}
+
+pub fn assign(assignment: &Assignment) -> Result<(), String> {
+    let current = lookup(assignment.name()).unwrap_or_default();
+    let value = assigned_value(current, assignment)?;
+    VARIABLES.lock().unwrap().find_or_insert(assignment.name()).value = Some(value);
+    Ok(())
+}
+
+pub fn set(name: &str, value: &str) -> Result<(), String> {
+    assign(&Assignment::Scalar(name.to_string(), value.to_string()))
+}
+
+/// Defines the variable in the innermost scope, shadowing outer definitions
+pub fn assign_local(assignment: &Assignment, exported: bool) -> Result<(), String> {
+    let current = lookup(assignment.name()).unwrap_or_default();
+    let local = Variable {
+        readonly: current.readonly,
+        integer: current.integer,
+        ..Default::default()
+    };
+    let value = assigned_value(local, assignment)?;
+
+    let mut table = VARIABLES.lock().unwrap();
+    let scope = table.scopes.last_mut().unwrap();
+    let var = scope.entry(assignment.name().to_string()).or_default();
+    var.value = Some(value);
+    var.exported |= exported;
+    Ok(())
+}
+
+pub fn unset(name: &str) -> Result<(), String> {
+    let mut table = VARIABLES.lock().unwrap();
+    if let Some(scope) = table.scopes.iter_mut().rev().find(|s| s.contains_key(name)) {
+        if scope[name].readonly {
+            return Err(format!("{}: cannot unset: readonly variable", name));
+        }
+        scope.remove(name);
+    }
+    Ok(())
+}
+
+pub fn unset_element(name: &str, subscript: &str) -> Result<(), String> {
+    let var = match lookup(name) {
+        Some(var) => var,
+        None => return Ok(()),
+    };
+    if var.readonly {
+        return Err(format!("{}: cannot unset: readonly variable", name));
+    }
+
+    match var.value {
+        Some(Value::Associative(mut array)) => {
+            array.remove(subscript);
+            set_attributes(name, |var| var.value = Some(Value::Associative(array)));
+            Ok(())
+        }
+        Some(Value::Indexed(mut array)) => {
+            let index = resolve_index(&array, subscript)?;
+            array.remove(&index);
+            set_attributes(name, |var| var.value = Some(Value::Indexed(array)));
+            Ok(())
+        }
+        Some(Value::Scalar(_)) if arith::evaluate(subscript)? == 0 => unset(name),
+        _ => Ok(()),
+    }
+}
+
+/// Changes the attributes of a variable, creating it without a value if needed
+pub fn set_attributes<F: FnOnce(&mut Variable)>(name: &str, f: F) {
+    f(VARIABLES.lock().unwrap().find_or_insert(name));
+}
+
+/// All visible variables sorted by name
+pub fn all() -> Vec<(String, Variable)> {
+    let table = VARIABLES.lock().unwrap();
+    let mut visible = HashMap::new();
+    for scope in &table.scopes {
+        for (name, var) in scope {
+            visible.insert(name.clone(), var.clone());
+        }
+    }
+    let mut vars: Vec<_> = visible.into_iter().collect();
+    vars.sort_by(|a, b| a.0.cmp(&b.0));
+    vars
+}
+
+/// The environment handed to child processes, arrays are never exported
+pub fn exported() -> Vec<(String, String)> {
+    all()
+        .into_iter()
+        .filter(|(_, var)| var.exported)
+        .filter_map(|(name, var)| match var.value {
+            Some(Value::Scalar(value)) => Some((name, value)),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn arg0() -> String {
+    VARIABLES.lock().unwrap().arg0.clone()
+}
+
+pub fn set_arg0(name: &str) {
+    VARIABLES.lock().unwrap().arg0 = name.to_string();
+}
+
+/// Positional parameters `$1`, `$2`, ...
+pub fn positional() -> Vec<String> {
+    VARIABLES.lock().unwrap().positional.clone()
+}
+
+pub fn set_positional(args: Vec<String>) {
+    VARIABLES.lock().unwrap().positional = args;
+}
+
+pub fn push_scope() {
+    VARIABLES.lock().unwrap().scopes.push(HashMap::new());
+}
+
+pub fn pop_scope() {
+    let mut table = VARIABLES.lock().unwrap();
+    if table.scopes.len() > 1 {
+        table.scopes.pop();
+    }
+}
+
+#[test]
+fn test_variable_scopes() {
+    set("TEST_SCOPE_VAR", "outer").unwrap();
+    push_scope();
+    let assignment = Assignment::Scalar("TEST_SCOPE_VAR".to_string(), "inner".to_string());
+    assign_local(&assignment, true).unwrap();
+    assert_eq!(get("TEST_SCOPE_VAR"), Some("inner".to_string()));
+    assert!(exported().contains(&("TEST_SCOPE_VAR".to_string(), "inner".to_string())));
+    pop_scope();
+    assert_eq!(get("TEST_SCOPE_VAR"), Some("outer".to_string()));
+    assert!(exported().iter().all(|(name, _)| name != "TEST_SCOPE_VAR"));
+
+    set_attributes("TEST_SCOPE_INT", |var| var.integer = true);
+    set("TEST_SCOPE_INT", "2 * (3 + TEST_SCOPE_INT)").unwrap();
+    assert_eq!(get("TEST_SCOPE_INT"), Some("6".to_string()));
+
+    set_attributes("TEST_SCOPE_RO", |var| var.readonly = true);
+    assert!(set("TEST_SCOPE_RO", "x").is_err());
+    assert!(unset("TEST_SCOPE_RO").is_err());
+}
+
+#[test]
+fn test_arrays() {
+    let elements = vec![(None, "a".to_string()), (Some("3".to_string()), "c".to_string()), (None, "d".to_string())];
+    assign(&Assignment::Array("TEST_ARRAY".to_string(), elements)).unwrap();
+    assign(&Assignment::Element("TEST_ARRAY".to_string(), "1".to_string(), "b".to_string())).unwrap();
+    let value = lookup("TEST_ARRAY").unwrap().value.unwrap();
+    assert_eq!(value.elements(), vec!["a", "b", "c", "d"]);
+    assert_eq!(value.keys(), vec!["0", "1", "3", "4"]);
+    assert_eq!(get("TEST_ARRAY"), Some("a".to_string()));
+    assert_eq!(get_element("TEST_ARRAY", "-1"), Ok(Some("d".to_string())));
+    assert_eq!(get_element("TEST_ARRAY", "2"), Ok(None));
+    unset_element("TEST_ARRAY", "3").unwrap();
+    assert_eq!(lookup("TEST_ARRAY").unwrap().value.unwrap().elements(), vec!["a", "b", "d"]);
+
+    set_attributes("TEST_ASSOC", |var| var.value = Some(Value::Associative(BTreeMap::new())));
+    assign(&Assignment::Element("TEST_ASSOC".to_string(), "k".to_string(), "v".to_string())).unwrap();
+    assert_eq!(get_element("TEST_ASSOC", "k"), Ok(Some("v".to_string())));
+    let elements = vec![(None, "v".to_string())];
+    assert!(assign(&Assignment::Array("TEST_ASSOC".to_string(), elements)).is_err());
+}